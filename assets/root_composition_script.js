@@ -1,6 +1,10 @@
 const DATA_STREAM_PUBLIC_TOKEN = "{{ token }}";
 const APPLY_CHECKBOX_KEY = "__APPLY_CHECKBOX";
 const TIMESTAMP_KEY = "__TIMESTAMP";
+const STATUS_KEY = "__STATUS";
+// a subcomp with a boolean control node by this name gets it checked while
+// the console's data is stale, e.g. to hide or dim it
+const STALE_CONTROL_NODE = "DSU Stale";
 
 /**
  * Handling code for receiving data from the DSU native application.
@@ -8,9 +12,11 @@ const TIMESTAMP_KEY = "__TIMESTAMP";
  * Receives data in the following format:
  * {
  *   "__TIMESTAMP": 923498324, // ms since epoch
+ *   "__STATUS": "live", // "stale" when the console stopped sending data
  *   "subcompName": {
  *     "__APPLY_CHECKBOX": "Control Node Name", // name of checkbox to use to determine whether this subcomp should be applied
  *     "Control Node 1": "Control Node value",
+ *     "Control Node 2": { "home": [1, 2] }, // from destination paths like home[1]
  *     // ...
  *   },
  *   // ...
@@ -28,7 +34,11 @@ const TIMESTAMP_KEY = "__TIMESTAMP";
         DATA_STREAM_PUBLIC_TOKEN,
         (status, data) => {
           if (status === "message") {
-            const { [TIMESTAMP_KEY]: timestamp, ...payload } = data.payload;
+            const {
+              [TIMESTAMP_KEY]: timestamp,
+              [STATUS_KEY]: dataStatus,
+              ...payload
+            } = data.payload;
             if (timestamp < lastReceived) {
               console.warn(
                 "Received an outdated message, skipping.",
@@ -63,7 +73,14 @@ const TIMESTAMP_KEY = "__TIMESTAMP";
                 }
 
                 if (shouldUpdate) {
-                  subComp.setPayload(value);
+                  if (STALE_CONTROL_NODE in currentPayload) {
+                    subComp.setPayload({
+                      ...value,
+                      [STALE_CONTROL_NODE]: dataStatus === "stale",
+                    });
+                  } else {
+                    subComp.setPayload(value);
+                  }
                 }
               } else {
                 console.error(
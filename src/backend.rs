@@ -1,6 +1,8 @@
+pub mod capture;
 pub mod mapping;
 pub mod network;
 pub mod profile;
 mod serializer;
+pub mod simulator;
 pub mod sports;
 pub mod stream;
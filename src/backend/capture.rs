@@ -0,0 +1,328 @@
+//! Recording of raw RTD traffic to `.dsucap` capture files.
+//!
+//! A capture is a header followed by any number of records, with all integers
+//! little-endian:
+//!
+//! | Field         | Type      | Description                                |
+//! | ------------- | --------- | ------------------------------------------ |
+//! | magic         | 7 bytes   | `DSUCAP` followed by a NUL byte            |
+//! | version       | u8        | [`CAPTURE_FORMAT_VERSION`]                 |
+//! | header length | u32       | Length of the header in bytes              |
+//! | header        | UTF-8     | JSON object matching [`CaptureHeader`]     |
+//!
+//! Each record is:
+//!
+//! | Field     | Type  | Description                                        |
+//! | --------- | ----- | -------------------------------------------------- |
+//! | timestamp | u64   | Microseconds since the capture started (monotonic) |
+//! | length    | u32   | Length of the frame in bytes                       |
+//! | frame     | bytes | The raw frame as read, from `SYN` through `ETB`    |
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::sports::DynamicSportType;
+
+pub const CAPTURE_FILE_EXTENSION: &str = "dsucap";
+pub const CAPTURE_MAGIC: &[u8; 7] = b"DSUCAP\0";
+pub const CAPTURE_FORMAT_VERSION: u8 = 1;
+/// The longest header read from a capture. Lengths come from the file, so
+/// they're checked before anything that long is allocated.
+const MAX_HEADER_LENGTH: usize = 64 * 1024;
+/// The longest frame read from a capture. RTD frames are a few hundred bytes,
+/// so anything near this means the file is corrupt.
+const MAX_FRAME_LENGTH: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    /// The version of the program used to record the capture.
+    pub ui_version: String,
+    pub sport_type: Option<DynamicSportType>,
+    /// The input the capture was recorded from, e.g. /dev/ttyUSB0
+    pub port: String,
+    /// Milliseconds since the Unix epoch when recording started
+    pub started_at: u64,
+}
+
+impl CaptureHeader {
+    pub fn new(sport_type: Option<DynamicSportType>, port: String) -> Self {
+        Self {
+            ui_version: env!("CARGO_PKG_VERSION").to_owned(),
+            sport_type,
+            port,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("what are you doing with your clock set so early?!")
+                .as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CaptureWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path, header: &CaptureHeader) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let header = serde_json::to_vec(header)?;
+        file.write_all(CAPTURE_MAGIC)?;
+        file.write_all(&[CAPTURE_FORMAT_VERSION])?;
+        file.write_all(&(header.len() as u32).to_le_bytes())?;
+        file.write_all(&header)?;
+        file.flush()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let timestamp = self.started.elapsed().as_micros() as u64;
+        self.file.write_all(&timestamp.to_le_bytes())?;
+        self.file.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.file.write_all(frame)?;
+        // flush every frame so the capture survives a crash mid-game
+        self.file.flush()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureRecord {
+    /// Time since the capture started
+    pub timestamp: Duration,
+    pub frame: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub header: CaptureHeader,
+    pub records: Vec<CaptureRecord>,
+}
+
+/// Reads a whole capture into memory.
+///
+/// A record cut short at the end of the file (e.g. if the program crashed while
+/// recording) is ignored rather than treated as an error.
+pub fn read_capture(path: &Path) -> io::Result<Capture> {
+    read_capture_from(BufReader::new(File::open(path)?), path)
+}
+
+fn read_capture_from(mut file: impl Read, path: &Path) -> io::Result<Capture> {
+    let mut magic = [0u8; 7];
+    file.read_exact(&mut magic)?;
+    if &magic != CAPTURE_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a Daktronics Singular UI capture file",
+        ));
+    }
+    let mut version = [0u8; 1];
+    file.read_exact(&mut version)?;
+    if version[0] != CAPTURE_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported capture format version {}", version[0]),
+        ));
+    }
+    let mut header = vec![0u8; read_length(&mut file, "header", MAX_HEADER_LENGTH)?];
+    file.read_exact(&mut header)?;
+    let header: CaptureHeader = serde_json::from_slice(&header)?;
+
+    let mut records = Vec::new();
+    loop {
+        let mut timestamp = [0u8; 8];
+        match file.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let mut frame = Vec::new();
+        let result = read_length(&mut file, "frame", MAX_FRAME_LENGTH).and_then(|length| {
+            frame.resize(length, 0);
+            file.read_exact(&mut frame)
+        });
+        match result {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                log::warn!(
+                    target: "capture",
+                    "Ignoring truncated record at the end of {}",
+                    path.display()
+                );
+                break;
+            }
+            Err(err) => return Err(err),
+        }
+        records.push(CaptureRecord {
+            timestamp: Duration::from_micros(u64::from_le_bytes(timestamp)),
+            frame,
+        });
+    }
+
+    Ok(Capture { header, records })
+}
+
+/// Reads the length of a header or frame, which can't be over `max`.
+fn read_length(file: &mut impl Read, what: &str, max: usize) -> io::Result<usize> {
+    let mut length = [0u8; 4];
+    file.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the capture is corrupt: a {} is {} bytes long, but can't be over {}",
+                what, length, max
+            ),
+        ));
+    }
+    Ok(length)
+}
+
+/// A shared handle to the capture being recorded, if any.
+///
+/// Cloned into the stream's data source so recording can be toggled while the
+/// stream is running.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder(Arc<Mutex<Option<CaptureWriter>>>);
+
+impl Recorder {
+    pub fn start(&self, path: &Path, header: &CaptureHeader) -> io::Result<()> {
+        let writer = CaptureWriter::create(path, header)?;
+        log::info!(target: "capture", "Recording raw RTD traffic to {}", path.display());
+        *self.0.lock().expect("recorder lock poisoned") = Some(writer);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        if let Some(writer) = self.0.lock().expect("recorder lock poisoned").take() {
+            log::info!(target: "capture", "Stopped recording to {}", writer.path.display());
+        }
+    }
+
+    /// The path of the capture being recorded, if recording.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.0
+            .lock()
+            .expect("recorder lock poisoned")
+            .as_ref()
+            .map(|writer| writer.path.clone())
+    }
+
+    /// Writes a frame to the capture if recording. Recording stops if the
+    /// write fails so a full disk doesn't produce an error for every packet.
+    pub fn record(&self, frame: &[u8]) {
+        let mut writer = self.0.lock().expect("recorder lock poisoned");
+        if let Some(ref mut capture) = *writer {
+            if let Err(err) = capture.write_frame(frame) {
+                log::error!(
+                    target: "capture",
+                    "Failed to write to {}, stopping recording: {}",
+                    capture.path.display(),
+                    err
+                );
+                *writer = None;
+            }
+        }
+    }
+}
+
+/// Where captures go when the path isn't given explicitly: the working
+/// directory, next to the log file.
+pub fn default_capture_path(profile_name: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}_{}.{}",
+        filenamify::filenamify(profile_name),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("what are you doing with your clock set so early?!")
+            .as_secs(),
+        CAPTURE_FILE_EXTENSION
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Vec<u8> {
+        let header = serde_json::to_vec(&CaptureHeader::new(None, "test".to_owned())).unwrap();
+        let mut bytes = CAPTURE_MAGIC.to_vec();
+        bytes.push(CAPTURE_FORMAT_VERSION);
+        bytes.extend((header.len() as u32).to_le_bytes());
+        bytes.extend(header);
+        bytes
+    }
+
+    fn record(timestamp: u64, length: u32, frame: &[u8]) -> Vec<u8> {
+        let mut bytes = timestamp.to_le_bytes().to_vec();
+        bytes.extend(length.to_le_bytes());
+        bytes.extend(frame);
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> io::Result<Capture> {
+        read_capture_from(bytes, Path::new("test.dsucap"))
+    }
+
+    #[test]
+    fn reads_records() {
+        let mut bytes = header();
+        bytes.extend(record(0, 3, b"one"));
+        bytes.extend(record(1500, 3, b"two"));
+        let capture = read(&bytes).unwrap();
+        assert_eq!(capture.header.port, "test");
+        let records: Vec<_> = capture
+            .records
+            .iter()
+            .map(|record| (record.timestamp, record.frame.as_slice()))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                (Duration::ZERO, b"one".as_slice()),
+                (Duration::from_micros(1500), b"two".as_slice())
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_a_truncated_last_record() {
+        let mut bytes = header();
+        bytes.extend(record(0, 3, b"one"));
+        bytes.extend(record(1500, 3, b"tw"));
+        assert_eq!(read(&bytes).unwrap().records.len(), 1);
+    }
+
+    #[test]
+    fn rejects_lengths_too_long_to_be_real() {
+        let mut bytes = header();
+        bytes.extend(record(0, u32::MAX, b"one"));
+        assert_eq!(read(&bytes).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut bytes = CAPTURE_MAGIC.to_vec();
+        bytes.push(CAPTURE_FORMAT_VERSION);
+        bytes.extend(u32::MAX.to_le_bytes());
+        assert_eq!(read(&bytes).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(
+            read(b"{\"name\": \"not a capture\"}").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
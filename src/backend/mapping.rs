@@ -1,14 +1,39 @@
 use std::{error::Error, fmt::Display};
 
+use change::{ChangeTracker, ItemOutput};
+use path::{DestinationPath, PathError};
+use repeat::RepeatMapping;
 use serde::{Deserialize, Serialize};
 use transformation::{Transformation, TransformationError};
 
+pub mod change;
+pub mod clock;
+pub mod expression;
+pub mod lookup;
+pub mod parsed;
+pub mod path;
+pub mod pattern;
+pub mod repeat;
+pub mod template;
+pub mod text;
+
 pub mod transformation {
-    use std::{error::Error, fmt::Display, num::ParseIntError};
+    use std::{error::Error, fmt::Display};
 
     use serde::{Deserialize, Serialize};
+    use serde_json::{Map, Value};
+
+    use super::{
+        clock::{ClockFormat, ClockParseError, ClockTime},
+        expression::{EvalError, Expression},
+        lookup::LookupTable,
+        parsed::Parsed,
+        pattern::{RegexExtract, RegexReplace},
+        template::Template,
+        text::{zero_pad, Padding, TextCase, Truncation},
+    };
 
-    #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
     pub enum Transformation {
         #[default]
         None,
@@ -20,6 +45,31 @@ pub mod transformation {
         AssertString,
         AssertNumber,
         AssertBoolean,
+        /// Computes the value from the whole source map, see
+        /// [`super::expression`]
+        Expression(Parsed<Expression>),
+        /// Builds text from several source fields, see [`super::template`]
+        Template(Parsed<Template>),
+        /// Translates codes into display text, see [`super::lookup`]
+        Lookup(LookupTable),
+        /// Extracts part of the text with a regular expression, see
+        /// [`super::pattern`]
+        RegexExtract(RegexExtract),
+        /// Replaces parts of the text with a regular expression
+        RegexReplace(RegexReplace),
+        /// Changes text to upper, lower or title case, see [`super::text`]
+        ChangeCase(TextCase),
+        /// Removes spaces from both ends of text
+        Trim,
+        Pad(Padding),
+        Truncate(Truncation),
+        /// Pads a number with zeros to this many whole digits
+        ZeroPad(usize),
+        /// Formats a clock time for display, see [`super::clock`]
+        ClockDisplay(ClockFormat),
+        /// Converts a clock time to milliseconds, e.g. for animating a
+        /// progress bar
+        TimeTotalMilliseconds,
     }
 
     impl Display for Transformation {
@@ -34,25 +84,65 @@ pub mod transformation {
                 Transformation::AssertString => "Assert string",
                 Transformation::AssertNumber => "Assert number",
                 Transformation::AssertBoolean => "Assert boolean",
+                Transformation::Expression(_) => "Compute with expression",
+                Transformation::Template(_) => "Format text from template",
+                Transformation::Lookup(_) => "Look up in table",
+                Transformation::RegexExtract(_) => "Extract with regex",
+                Transformation::RegexReplace(_) => "Replace with regex",
+                Transformation::ChangeCase(_) => "Change case",
+                Transformation::Trim => "Trim spaces",
+                Transformation::Pad(_) => "Pad to width",
+                Transformation::Truncate(_) => "Truncate to width",
+                Transformation::ZeroPad(_) => "Pad number with zeros",
+                Transformation::ClockDisplay(_) => "Format clock for display",
+                Transformation::TimeTotalMilliseconds => "Convert time to milliseconds",
             })
         }
     }
 
     impl Transformation {
-        pub const ALL: [Transformation; 8] = [
+        // some transformations keep what they parse (see [`Parsed`]), which
+        // is empty in each copy of the constant
+        #[allow(clippy::declare_interior_mutable_const)]
+        pub const ALL: [Transformation; 21] = [
             Transformation::None,
             Transformation::TimeMinutes,
             Transformation::TimeSeconds,
+            Transformation::TimeSecondsRoundUp,
             Transformation::TimeTenths,
             Transformation::AppendOrdinalSuffix,
             Transformation::AssertString,
             Transformation::AssertNumber,
             Transformation::AssertBoolean,
+            Transformation::Expression(Parsed::EMPTY),
+            Transformation::Template(Parsed::EMPTY),
+            Transformation::Lookup(LookupTable::EMPTY),
+            Transformation::RegexExtract(RegexExtract::EMPTY),
+            Transformation::RegexReplace(RegexReplace::EMPTY),
+            Transformation::ChangeCase(TextCase::Upper),
+            Transformation::Trim,
+            Transformation::Pad(Padding::DEFAULT),
+            Transformation::Truncate(Truncation::DEFAULT),
+            Transformation::ZeroPad(2),
+            Transformation::ClockDisplay(ClockFormat::DEFAULT),
+            Transformation::TimeTotalMilliseconds,
         ];
 
+        /// Whether the transformation reads its own fields from the source
+        /// map, so the mapping item's source field is optional.
+        pub fn reads_source_map(&self) -> bool {
+            matches!(
+                self,
+                Transformation::Expression(_) | Transformation::Template(_)
+            )
+        }
+
+        /// Transforms `value`, the mapping item's source value. `source` is the
+        /// whole source map, for transformations that read other fields.
         pub fn transform(
             &self,
             value: &serde_json::Value,
+            source: &Map<String, Value>,
         ) -> Result<serde_json::Value, TransformationError> {
             match self {
                 Transformation::None => {
@@ -63,88 +153,20 @@ pub mod transformation {
                     }
                 }
                 Transformation::TimeMinutes => {
-                    let minute_seconds: Vec<_> = value
-                        .as_str()
-                        .ok_or_else(|| TransformationError::UnexpectedSourceType(value.clone()))?
-                        .trim()
-                        // Take off the tenths
-                        .split(".")
-                        .next()
-                        .ok_or(TransformationError::DataExtractionFailed)?
-                        .split(":")
-                        .collect();
-                    Ok(serde_json::Value::Number(if minute_seconds.len() < 2 {
-                        // no minutes component exists, so 0
-                        serde_json::Number::from(0)
-                    } else {
-                        serde_json::Number::from(
-                            minute_seconds
-                                // Take off the seconds
-                                [0]
-                            // Parse as a number
-                            .parse::<i32>()
-                            .map_err(TransformationError::ParseInt)?,
-                        )
-                    }))
+                    Ok(serde_json::Value::from(clock_time(value)?.total_minutes()))
                 }
                 Transformation::TimeSeconds => {
-                    Ok(serde_json::Value::Number(serde_json::Number::from(
-                        value
-                            .as_str()
-                            .ok_or_else(|| {
-                                TransformationError::UnexpectedSourceType(value.clone())
-                            })?
-                            .trim()
-                            // Take off the tenths
-                            .split(".")
-                            .next()
-                            .ok_or(TransformationError::DataExtractionFailed)?
-                            // Take off the seconds
-                            .split(":")
-                            .last() // there might not be a minutes component
-                            .ok_or(TransformationError::DataExtractionFailed)?
-                            // Parse as a number
-                            .parse::<i32>()
-                            .map_err(TransformationError::ParseInt)?,
-                    )))
+                    Ok(serde_json::Value::from(clock_time(value)?.seconds()))
                 }
                 Transformation::TimeSecondsRoundUp => {
-                    // get the fractional seconds value from the scoreboard by
-                    // combining the seconds and tenths parts
-                    let seconds = Transformation::transform(&Transformation::TimeSeconds, value)?
-                        .as_number()
-                        .unwrap() // rationale: look at the code above, it's a number.
-                        .as_f64()
-                        .unwrap() // rationale: f64 can fit all i32s, and it was parsed as i32
-                        + Transformation::transform(&Transformation::TimeTenths, value)?
-                            .as_number()
-                            .unwrap() // rationales same as above
-                            .as_f64()
-                            .unwrap()
-                            / 10.0;
-                    // Ceiling it and cast to i32. that should be safe?
-                    Ok(serde_json::Value::Number(serde_json::Number::from(
-                        seconds.ceil() as i32,
-                    )))
+                    let time = clock_time(value)?;
+                    // a partial second counts as a whole one, like a
+                    // countdown showing 1 until it reaches 0
+                    let seconds = time.seconds() + u64::from(time.total_millis() % 1000 != 0);
+                    Ok(serde_json::Value::from(seconds))
                 }
                 Transformation::TimeTenths => {
-                    let split: Vec<_> = value
-                        .as_str()
-                        .ok_or_else(|| TransformationError::UnexpectedSourceType(value.clone()))?
-                        .trim()
-                        // Take off the tenths
-                        .split(".")
-                        .collect();
-                    Ok(serde_json::Value::Number(if split.len() > 1 {
-                        serde_json::Number::from(
-                            split[2] // Parse as a number
-                                .parse::<i32>()
-                                .map_err(TransformationError::ParseInt)?,
-                        )
-                    } else {
-                        // No tenths component exists
-                        serde_json::Number::from(0)
-                    }))
+                    Ok(serde_json::Value::from(clock_time(value)?.tenths()))
                 }
                 Transformation::AppendOrdinalSuffix => {
                     let mut s = value
@@ -183,15 +205,66 @@ pub mod transformation {
                         return Ok(value.clone());
                     }
                 }
+                Transformation::Expression(expression) => expression
+                    .parsed()
+                    .map_err(|err| TransformationError::Expression(err.to_string()))?
+                    .evaluate(value, source)
+                    .map_err(|err| match err {
+                        // missing data, so exclude_incomplete_data applies
+                        EvalError::Null => TransformationError::UnexpectedSourceType(Value::Null),
+                        err => TransformationError::Expression(err.to_string()),
+                    }),
+                Transformation::Template(template) => template
+                    .parsed()
+                    .map_err(|err| TransformationError::Template(err.to_string()))?
+                    .render(value, source)
+                    .map(serde_json::Value::String),
+                Transformation::Lookup(table) => table.lookup(value),
+                Transformation::RegexExtract(extract) => extract.extract(value),
+                Transformation::RegexReplace(replace) => replace.replace(value),
+                Transformation::ChangeCase(case) => case.apply(value),
+                Transformation::Trim => match value {
+                    serde_json::Value::String(string) => {
+                        Ok(serde_json::Value::String(string.trim().to_owned()))
+                    }
+                    serde_json::Value::Null => {
+                        Err(TransformationError::UnexpectedSourceType(value.clone()))
+                    }
+                    // nothing to trim
+                    _ => Ok(value.clone()),
+                },
+                Transformation::Pad(padding) => padding.pad(value),
+                Transformation::Truncate(truncation) => truncation.truncate(value),
+                Transformation::ZeroPad(digits) => zero_pad(value, *digits),
+                // a blank clock is shown blank
+                Transformation::ClockDisplay(format) => Ok(serde_json::Value::String(
+                    ClockTime::parse_value(value)?
+                        .map(|time| format.format(time))
+                        .unwrap_or_default(),
+                )),
+                Transformation::TimeTotalMilliseconds => {
+                    Ok(serde_json::Value::from(clock_time(value)?.total_millis()))
+                }
             }
         }
     }
 
+    /// The clock time in a source value. A blank clock counts as missing data.
+    fn clock_time(value: &Value) -> Result<ClockTime, TransformationError> {
+        ClockTime::parse_value(value)?.ok_or(TransformationError::UnexpectedSourceType(Value::Null))
+    }
+
     #[derive(Debug)]
     pub enum TransformationError {
         UnexpectedSourceType(serde_json::Value),
-        DataExtractionFailed,
-        ParseInt(ParseIntError),
+        Expression(String),
+        Template(String),
+        /// A field read by the transformation has no data yet
+        MissingField(String),
+        NoLookupMatch(String),
+        Pattern(String),
+        NoPatternMatch(String),
+        Clock(ClockParseError),
     }
 
     impl Display for TransformationError {
@@ -209,8 +282,19 @@ pub mod transformation {
                         serde_json::Value::Object(_) => "object",
                     }
                 ),
-                TransformationError::DataExtractionFailed => write!(f, "data extraction failed"),
-                TransformationError::ParseInt(err) => write!(f, "failed to parse int: {}", err),
+                TransformationError::Expression(err) => write!(f, "expression failed: {}", err),
+                TransformationError::Template(err) => write!(f, "template failed: {}", err),
+                TransformationError::MissingField(field) => {
+                    write!(f, "source field {} has no data yet", field)
+                }
+                TransformationError::NoLookupMatch(key) => {
+                    write!(f, "{:?} isn't in the lookup table", key)
+                }
+                TransformationError::Pattern(err) => write!(f, "invalid regex: {}", err),
+                TransformationError::NoPatternMatch(text) => {
+                    write!(f, "the regex doesn't match {:?}", text)
+                }
+                TransformationError::Clock(err) => err.fmt(f),
             }
         }
     }
@@ -221,44 +305,96 @@ pub mod transformation {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Mapping {
     pub items: Vec<MappingItem>,
+    #[serde(default)]
+    pub repeats: Vec<RepeatMapping>,
 }
 
 impl Mapping {
+    /// Maps `source`. `state` is what's remembered from the payloads before,
+    /// and should be kept between calls. What the payload changes is staged
+    /// in `state` until it's committed (see [`MappingState::commit`]).
     pub fn map(
         &self,
         source: &serde_json::Value,
         exclude_incomplete_data: bool,
+        state: &mut MappingState,
     ) -> Result<serde_json::Value, MapError> {
         let source_map = source.as_object().ok_or(MapError::SourceNotMap)?;
         // map could be underfilled if fields are disabled, but that's okay
         let mut destination = serde_json::Map::with_capacity(self.items.len());
+        let mut written_paths = Vec::with_capacity(self.items.len() + self.repeats.len());
         for item in &self.items {
-            match item.map(source_map, &mut destination) {
-                Ok(_) => (),
-                Err(MapError::Transformation(
-                    destination_field,
-                    TransformationError::UnexpectedSourceType(serde_json::Value::Null),
-                )) => {
-                    if !exclude_incomplete_data {
-                        return Err(MapError::Transformation(
-                            destination_field,
-                            TransformationError::UnexpectedSourceType(serde_json::Value::Null),
-                        ));
-                    }
-                }
-                Err(err) => return Err(err),
+            if let Some(value) = item.map(source_map, exclude_incomplete_data, state)? {
+                insert_at_path(
+                    &mut destination,
+                    &mut written_paths,
+                    &item.destination_field,
+                    value,
+                )?;
+            }
+        }
+        for repeat in &self.repeats {
+            if let Some(value) = repeat.map(source_map, exclude_incomplete_data, state)? {
+                insert_at_path(
+                    &mut destination,
+                    &mut written_paths,
+                    &repeat.destination_field,
+                    value,
+                )?;
             }
         }
         Ok(serde_json::Value::Object(destination))
     }
+
+    /// Escapes every destination field so it's read as a plain field, for
+    /// mappings saved before destination fields were paths.
+    pub fn escape_destination_fields(&mut self) {
+        for item in &mut self.items {
+            item.destination_field = path::escape(&item.destination_field);
+        }
+        for repeat in &mut self.repeats {
+            repeat.destination_field = path::escape(&repeat.destination_field);
+            repeat.sort_by = path::escape(&repeat.sort_by);
+            for item in &mut repeat.items {
+                item.destination_field = path::escape(&item.destination_field);
+            }
+        }
+    }
+}
+
+/// Inserts `value` at the destination path `field`, unless it overlaps one of
+/// `written_paths`, which it's added to.
+fn insert_at_path(
+    destination: &mut serde_json::Map<String, serde_json::Value>,
+    written_paths: &mut Vec<DestinationPath>,
+    field: &str,
+    value: serde_json::Value,
+) -> Result<(), MapError> {
+    let path = DestinationPath::parse(field).map_err(MapError::DestinationPath)?;
+    if written_paths.iter().any(|written| written.overlaps(&path))
+        || !path.insert(destination, value)
+    {
+        return Err(MapError::DestinationFieldAlreadyPresent(field.to_owned()));
+    }
+    written_paths.push(path);
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MappingItem {
     pub enabled: bool,
     pub source_field: String,
-    pub transformation: Transformation,
+    /// Applied in order, each to the result of the one before
+    #[serde(
+        alias = "transformation",
+        deserialize_with = "deserialize_transformations"
+    )]
+    pub transformations: Vec<Transformation>,
     pub destination_field: String,
+    #[serde(default)]
+    pub missing_data: MissingDataPolicy,
+    #[serde(default)]
+    pub output: ItemOutput,
 }
 
 impl Default for MappingItem {
@@ -266,36 +402,200 @@ impl Default for MappingItem {
         MappingItem {
             enabled: true,
             source_field: Default::default(),
-            transformation: Default::default(),
+            transformations: vec![Default::default()],
             destination_field: Default::default(),
+            missing_data: Default::default(),
+            output: Default::default(),
         }
     }
 }
 
+/// What a mapping remembers between payloads. What a payload changes is
+/// staged until it's committed, so a payload that fails partway through
+/// (and isn't sent) doesn't change what's remembered.
+#[derive(Debug, Default)]
+pub struct MappingState {
+    /// The last value output to each destination field, for items that keep
+    /// it when their data is missing
+    last_values: serde_json::Map<String, serde_json::Value>,
+    /// Values output by the payload being mapped, until it's committed
+    staged_last_values: serde_json::Map<String, serde_json::Value>,
+    pub changes: ChangeTracker,
+}
+
+impl MappingState {
+    /// Keeps what the payload just mapped changed, once it's going to be sent.
+    pub fn commit(&mut self) {
+        self.last_values.append(&mut self.staged_last_values);
+        self.changes.commit();
+    }
+
+    /// Forgets what the payload just mapped changed, since it won't be sent.
+    pub fn discard(&mut self) {
+        self.staged_last_values.clear();
+        self.changes.discard();
+    }
+
+    fn last_value(&self, destination_field: &str) -> Option<&serde_json::Value> {
+        self.staged_last_values
+            .get(destination_field)
+            .or_else(|| self.last_values.get(destination_field))
+    }
+}
+
+/// What a mapping item outputs when its source data is null or a field it
+/// reads has no data yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissingDataPolicy {
+    /// Leave it out or fail the payload, per the profile's
+    /// `exclude_incomplete_data`
+    #[default]
+    ProfileDefault,
+    /// Leave the destination field out of the payload
+    Drop,
+    /// Fail the whole payload
+    Fail,
+    /// Output this text instead
+    Fallback(String),
+    /// Output the last value mapped, or leave it out if there's none yet
+    KeepLast,
+}
+
+impl MissingDataPolicy {
+    pub const ALL: [MissingDataPolicy; 5] = [
+        MissingDataPolicy::ProfileDefault,
+        MissingDataPolicy::Drop,
+        MissingDataPolicy::Fail,
+        MissingDataPolicy::Fallback(String::new()),
+        MissingDataPolicy::KeepLast,
+    ];
+}
+
+impl Display for MissingDataPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MissingDataPolicy::ProfileDefault => "If missing: profile default",
+            MissingDataPolicy::Drop => "If missing: leave out",
+            MissingDataPolicy::Fail => "If missing: fail",
+            MissingDataPolicy::Fallback(_) => "If missing: use fallback",
+            MissingDataPolicy::KeepLast => "If missing: keep last value",
+        })
+    }
+}
+
+/// Profiles from before transformations could be chained have a single one.
+fn deserialize_transformations<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Transformation>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        Many(Vec<Transformation>),
+        One(Transformation),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(transformations) => transformations,
+        OneOrMany::One(transformation) => vec![transformation],
+    })
+}
+
 impl MappingItem {
+    /// The value to output to the destination field, or `None` if it's left
+    /// out.
     pub fn map(
         &self,
         source: &serde_json::Map<String, serde_json::Value>,
-        destination: &mut serde_json::Map<String, serde_json::Value>,
-    ) -> Result<(), MapError> {
+        exclude_incomplete_data: bool,
+        state: &mut MappingState,
+    ) -> Result<Option<serde_json::Value>, MapError> {
+        self.map_field(
+            &self.source_field,
+            &self.destination_field,
+            source,
+            exclude_incomplete_data,
+            state,
+        )
+    }
+
+    /// Like [`MappingItem::map`], but reads `source_field` instead of the
+    /// item's and names the output `destination_field` in errors and state.
+    fn map_field(
+        &self,
+        source_field: &str,
+        destination_field: &str,
+        source: &serde_json::Map<String, serde_json::Value>,
+        exclude_incomplete_data: bool,
+        state: &mut MappingState,
+    ) -> Result<Option<serde_json::Value>, MapError> {
         if self.enabled {
-            let source_value = source
-                .get(&self.source_field)
-                .ok_or_else(|| MapError::SourceFieldNonExistent(self.source_field.clone()))?;
-            let transformed = self
-                .transformation
-                .transform(source_value)
-                .map_err(|x| MapError::Transformation(self.destination_field.clone(), x))?;
-            let previous_value = destination.insert(self.destination_field.clone(), transformed);
-            if let Some(_) = previous_value {
-                Err(MapError::DestinationFieldAlreadyPresent(
-                    self.destination_field.clone(),
-                ))
+            // these read the fields they need, so the source field is optional
+            let reads_source_map = self
+                .transformations
+                .first()
+                .is_some_and(Transformation::reads_source_map);
+            let source_value = if reads_source_map && source_field.is_empty() {
+                &serde_json::Value::Null
             } else {
-                Ok(())
-            }
+                source
+                    .get(source_field)
+                    .ok_or_else(|| MapError::SourceFieldNonExistent(source_field.to_owned()))?
+            };
+            let transformed = match self.transform(source_value, source) {
+                Ok(transformed) => {
+                    let output =
+                        self.output
+                            .output(destination_field, transformed, &mut state.changes);
+                    if self.missing_data == MissingDataPolicy::KeepLast {
+                        state
+                            .staged_last_values
+                            .insert(destination_field.to_owned(), output.clone());
+                    }
+                    output
+                }
+                Err(
+                    err @ (TransformationError::UnexpectedSourceType(serde_json::Value::Null)
+                    | TransformationError::MissingField(_)),
+                ) => match &self.missing_data {
+                    MissingDataPolicy::ProfileDefault if exclude_incomplete_data => {
+                        return Ok(None)
+                    }
+                    MissingDataPolicy::Drop => return Ok(None),
+                    MissingDataPolicy::Fallback(fallback) => {
+                        serde_json::Value::String(fallback.clone())
+                    }
+                    MissingDataPolicy::KeepLast => match state.last_value(destination_field) {
+                        Some(last_value) => last_value.clone(),
+                        None => return Ok(None),
+                    },
+                    MissingDataPolicy::ProfileDefault | MissingDataPolicy::Fail => {
+                        return Err(MapError::Transformation(destination_field.to_owned(), err))
+                    }
+                },
+                Err(err) => {
+                    return Err(MapError::Transformation(destination_field.to_owned(), err))
+                }
+            };
+            Ok(Some(transformed))
         } else {
-            Ok(())
+            Ok(None)
+        }
+    }
+
+    /// Runs the transformations in order. Without any, the value is passed
+    /// through like [`Transformation::None`] does.
+    fn transform(
+        &self,
+        value: &serde_json::Value,
+        source: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<serde_json::Value, TransformationError> {
+        match self.transformations.split_first() {
+            None => Transformation::None.transform(value, source),
+            Some((first, rest)) => rest
+                .iter()
+                .try_fold(first.transform(value, source)?, |value, transformation| {
+                    transformation.transform(&value, source)
+                }),
         }
     }
 }
@@ -305,6 +605,7 @@ pub enum MapError {
     SourceNotMap,
     SourceFieldNonExistent(String),
     DestinationFieldAlreadyPresent(String),
+    DestinationPath(PathError),
     Transformation(String, TransformationError),
 }
 
@@ -318,6 +619,7 @@ impl Display for MapError {
             MapError::DestinationFieldAlreadyPresent(field) => {
                 write!(f, "destination field {} is already present in the output; maybe there's a duplicate?", field)
             }
+            MapError::DestinationPath(err) => write!(f, "{}", err),
             MapError::Transformation(attempted_destination_field, err) => write!(
                 f,
                 "transformation error for destination field {}: {}",
@@ -328,3 +630,51 @@ impl Display for MapError {
 }
 
 impl Error for MapError {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_value_only_from_payloads_that_mapped() {
+        let mapping = Mapping {
+            items: vec![
+                MappingItem {
+                    source_field: "clock".to_owned(),
+                    destination_field: "clock".to_owned(),
+                    missing_data: MissingDataPolicy::KeepLast,
+                    ..Default::default()
+                },
+                MappingItem {
+                    source_field: "period".to_owned(),
+                    destination_field: "period".to_owned(),
+                    missing_data: MissingDataPolicy::Fail,
+                    ..Default::default()
+                },
+            ],
+            repeats: vec![],
+        };
+        let mut state = MappingState::default();
+        let mut map = |source: serde_json::Value| {
+            let result = mapping.map(&source, false, &mut state);
+            match result {
+                Ok(_) => state.commit(),
+                Err(_) => state.discard(),
+            }
+            result.ok()
+        };
+
+        assert_eq!(
+            map(json!({ "clock": "12:00", "period": 1 })),
+            Some(json!({ "clock": "12:00", "period": 1 }))
+        );
+        // fails on the period after the clock has mapped
+        assert_eq!(map(json!({ "clock": "11:59", "period": null })), None);
+        assert_eq!(
+            map(json!({ "clock": null, "period": 1 })),
+            Some(json!({ "clock": "12:00", "period": 1 }))
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Outputs that react to a value changing rather than output it, so the
+//! composition can trigger an animation (e.g. a goal scored) on the edge.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+fn default_hold_ms() -> u64 {
+    2000
+}
+
+/// What a mapping item outputs from its transformed value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemOutput {
+    /// The value itself
+    #[default]
+    Value,
+    /// `true` for `hold_ms` after the value changes, otherwise `false`
+    Pulse {
+        only_on_increase: bool,
+        #[serde(default = "default_hold_ms")]
+        hold_ms: u64,
+    },
+    /// How many times the value has changed since the stream started
+    ChangeCount { only_on_increase: bool },
+}
+
+impl ItemOutput {
+    pub const ALL: [ItemOutput; 3] = [
+        ItemOutput::Value,
+        ItemOutput::Pulse {
+            only_on_increase: false,
+            hold_ms: 2000,
+        },
+        ItemOutput::ChangeCount {
+            only_on_increase: false,
+        },
+    ];
+
+    /// Outputs `value` for the destination field `field`, staging it in
+    /// `changes` to compare with the next one. The first value seen isn't a
+    /// change.
+    pub fn output(&self, field: &str, value: Value, changes: &mut ChangeTracker) -> Value {
+        match *self {
+            ItemOutput::Value => value,
+            ItemOutput::Pulse {
+                only_on_increase,
+                hold_ms,
+            } => {
+                let now = Instant::now();
+                let state = changes.observe(field, value, only_on_increase);
+                if state.changed {
+                    state.pulse_end = Some(now + Duration::from_millis(hold_ms));
+                }
+                // a hold of 0 is still on for the payload with the change
+                let on = state.changed || state.pulse_end.is_some_and(|pulse_end| now < pulse_end);
+                if !on {
+                    state.pulse_end = None;
+                }
+                Value::Bool(on)
+            }
+            ItemOutput::ChangeCount { only_on_increase } => {
+                Value::from(changes.observe(field, value, only_on_increase).count)
+            }
+        }
+    }
+}
+
+impl Display for ItemOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ItemOutput::Value => "Output the value",
+            ItemOutput::Pulse { .. } => "Output true when it changes",
+            ItemOutput::ChangeCount { .. } => "Output a count of changes",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChangeState {
+    value: Value,
+    /// Whether the latest value was a change
+    changed: bool,
+    count: u64,
+    /// When the pulse that's on ends, until it's been output off
+    pulse_end: Option<Instant>,
+}
+
+/// The last value seen for each change-tracking destination field.
+///
+/// Values from the payload being mapped are staged, and only compared with
+/// later payloads once they're committed. A payload that fails to map is
+/// discarded, so its changes are still seen when it maps.
+#[derive(Debug, Default)]
+pub struct ChangeTracker {
+    fields: HashMap<String, ChangeState>,
+    staged: HashMap<String, ChangeState>,
+}
+
+impl ChangeTracker {
+    /// When the first pulse that's on ends, so the payload can be mapped
+    /// again to turn it off. It may already be past.
+    pub fn next_pulse_end(&self) -> Option<Instant> {
+        self.fields
+            .values()
+            .filter_map(|state| state.pulse_end)
+            .min()
+    }
+
+    /// Keeps the values staged by the payload just mapped.
+    pub fn commit(&mut self) {
+        self.fields.extend(self.staged.drain());
+    }
+
+    /// Forgets the values staged by a payload that failed to map.
+    pub fn discard(&mut self) {
+        self.staged.clear();
+    }
+
+    fn observe(&mut self, field: &str, value: Value, only_on_increase: bool) -> &mut ChangeState {
+        let previous = self
+            .staged
+            .remove(field)
+            .or_else(|| self.fields.get(field).cloned());
+        let state = match previous {
+            None => ChangeState {
+                value,
+                changed: false,
+                count: 0,
+                pulse_end: None,
+            },
+            Some(mut state) => {
+                state.changed = if only_on_increase {
+                    // a drop (e.g. a corrected score) isn't a change, but the
+                    // next increase is compared to it
+                    number(&value)
+                        .zip(number(&state.value))
+                        .is_some_and(|(new, old)| new > old)
+                } else {
+                    value != state.value
+                };
+                if state.changed {
+                    state.count += 1;
+                }
+                state.value = value;
+                state
+            }
+        };
+        self.staged
+            .entry(field.to_owned())
+            .insert_entry(state)
+            .into_mut()
+    }
+}
+
+/// Console numbers often come through as padded text.
+pub(super) fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ChangeTracker, ItemOutput};
+
+    #[test]
+    fn counts_committed_changes() {
+        let output = ItemOutput::ChangeCount {
+            only_on_increase: false,
+        };
+        let mut changes = ChangeTracker::default();
+        for (value, count) in [(1, 0), (1, 0), (2, 1), (3, 2), (3, 2), (1, 3)] {
+            assert_eq!(
+                output.output("goals", json!(value), &mut changes),
+                json!(count)
+            );
+            changes.commit();
+        }
+    }
+
+    #[test]
+    fn only_counts_increases_when_asked() {
+        let output = ItemOutput::ChangeCount {
+            only_on_increase: true,
+        };
+        let mut changes = ChangeTracker::default();
+        for (value, count) in [(" 1", 0), (" 2", 1), (" 1", 1), (" 2", 2), ("", 2)] {
+            assert_eq!(
+                output.output("goals", json!(value), &mut changes),
+                json!(count)
+            );
+            changes.commit();
+        }
+    }
+
+    #[test]
+    fn sees_changes_again_after_a_failed_payload() {
+        let count = ItemOutput::ChangeCount {
+            only_on_increase: false,
+        };
+        let pulse = ItemOutput::Pulse {
+            only_on_increase: false,
+            hold_ms: 0,
+        };
+        let mut changes = ChangeTracker::default();
+        count.output("goals", json!(1), &mut changes);
+        pulse.output("goal_scored", json!(1), &mut changes);
+        changes.commit();
+
+        assert_eq!(count.output("goals", json!(2), &mut changes), json!(1));
+        assert_eq!(
+            pulse.output("goal_scored", json!(2), &mut changes),
+            json!(true)
+        );
+        changes.discard();
+
+        assert_eq!(count.output("goals", json!(2), &mut changes), json!(1));
+        assert_eq!(
+            pulse.output("goal_scored", json!(2), &mut changes),
+            json!(true)
+        );
+        changes.commit();
+
+        assert_eq!(count.output("goals", json!(2), &mut changes), json!(1));
+        assert_eq!(
+            pulse.output("goal_scored", json!(2), &mut changes),
+            json!(false)
+        );
+    }
+}
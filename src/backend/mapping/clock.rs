@@ -0,0 +1,439 @@
+//! Clock times as the console sends them, e.g. `12:00`, ` 4:05`, `45.3` or
+//! `1:02:03`, and formatting them for display the way broadcasts do.
+
+use std::{error::Error, fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transformation::TransformationError;
+
+const MILLIS_PER_SECOND: u64 = 1000;
+const MILLIS_PER_MINUTE: u64 = 60 * MILLIS_PER_SECOND;
+const MILLIS_PER_HOUR: u64 = 60 * MILLIS_PER_MINUTE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockParseError {
+    pub text: String,
+    pub message: &'static str,
+}
+
+impl Display for ClockParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} isn't a clock time: {}", self.text, self.message)
+    }
+}
+
+impl Error for ClockParseError {}
+
+/// A time on the console's clock, to the millisecond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClockTime {
+    millis: u64,
+}
+
+impl ClockTime {
+    /// Parses `H:MM:SS`, `M:SS` or `SS`, where the seconds can have a
+    /// fraction (usually tenths) and the text can be padded with spaces.
+    /// Components left blank by the console, like the minutes of ` :45`, are
+    /// zero. Returns `None` if the clock is blank.
+    pub fn parse(text: &str) -> Result<Option<Self>, ClockParseError> {
+        let error = |message| ClockParseError {
+            text: text.to_owned(),
+            message,
+        };
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        let components = trimmed.split(':').map(str::trim).collect::<Vec<_>>();
+        let (hours, minutes, seconds) = match components[..] {
+            [seconds] => ("", "", seconds),
+            [minutes, seconds] => ("", minutes, seconds),
+            [hours, minutes, seconds] => (hours, minutes, seconds),
+            _ => return Err(error("there are too many colons")),
+        };
+        let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+        let number = |component: &str| -> Result<u64, ClockParseError> {
+            if component.is_empty() {
+                Ok(0)
+            } else if component.chars().all(|char| char.is_ascii_digit()) {
+                component.parse().map_err(|_| error("a number is too big"))
+            } else {
+                Err(error("there's something other than digits"))
+            }
+        };
+        let (hours, minutes, seconds) = (number(hours)?, number(minutes)?, number(seconds)?);
+        if components.len() > 1 && seconds >= 60 {
+            return Err(error("the seconds are 60 or more"));
+        }
+        if components.len() > 2 && minutes >= 60 {
+            return Err(error("the minutes are 60 or more"));
+        }
+        if !fraction.chars().all(|char| char.is_ascii_digit()) {
+            return Err(error("there's something other than digits"));
+        }
+        // only the first three digits of the fraction are significant
+        let fraction_millis = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(3)
+            .fold(0, |millis, digit| millis * 10 + u64::from(digit - b'0'));
+        hours
+            .checked_mul(MILLIS_PER_HOUR)
+            .zip(minutes.checked_mul(MILLIS_PER_MINUTE))
+            .zip(seconds.checked_mul(MILLIS_PER_SECOND))
+            .and_then(|((hours, minutes), seconds)| {
+                hours
+                    .checked_add(minutes)?
+                    .checked_add(seconds)?
+                    .checked_add(fraction_millis)
+            })
+            .map(|millis| Some(Self { millis }))
+            .ok_or_else(|| error("a number is too big"))
+    }
+
+    /// Parses the text of a source value, see [`ClockTime::parse`].
+    pub fn parse_value(value: &Value) -> Result<Option<Self>, TransformationError> {
+        let text = value
+            .as_str()
+            .ok_or_else(|| TransformationError::UnexpectedSourceType(value.clone()))?;
+        Self::parse(text).map_err(TransformationError::Clock)
+    }
+
+    pub fn total_millis(&self) -> u64 {
+        self.millis
+    }
+
+    pub fn hours(&self) -> u64 {
+        self.millis / MILLIS_PER_HOUR
+    }
+
+    /// The minutes, including those in whole hours, e.g. 75 for `1:15:00`
+    pub fn total_minutes(&self) -> u64 {
+        self.millis / MILLIS_PER_MINUTE
+    }
+
+    /// The minutes past the hour
+    pub fn minutes(&self) -> u64 {
+        self.total_minutes() % 60
+    }
+
+    /// The whole seconds past the minute
+    pub fn seconds(&self) -> u64 {
+        self.millis / MILLIS_PER_SECOND % 60
+    }
+
+    /// The tenths of a second past the second
+    pub fn tenths(&self) -> u64 {
+        self.millis % MILLIS_PER_SECOND / 100
+    }
+}
+
+impl From<Duration> for ClockTime {
+    /// Rounds down to the millisecond, and saturates at the longest time.
+    fn from(duration: Duration) -> Self {
+        Self {
+            millis: duration.as_millis().try_into().unwrap_or(u64::MAX),
+        }
+    }
+}
+
+impl From<ClockTime> for Duration {
+    fn from(time: ClockTime) -> Self {
+        Duration::from_millis(time.millis)
+    }
+}
+
+/// How to display a clock time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockFormat {
+    /// Below this many seconds, show seconds and tenths (`SS.t`) instead of
+    /// minutes and seconds (`M:SS`). 0 never shows tenths.
+    pub tenths_below_seconds: u64,
+    /// Whether to show hours (`H:MM:SS`) once there are any, instead of
+    /// counting minutes past 60 like soccer clocks do
+    pub show_hours: bool,
+    /// Whether to leave out the leading zero, showing `5.3` instead of `05.3`
+    /// and `:45` instead of `0:45`
+    pub suppress_leading_zeros: bool,
+}
+
+impl ClockFormat {
+    pub const DEFAULT: ClockFormat = ClockFormat {
+        tenths_below_seconds: 60,
+        show_hours: false,
+        suppress_leading_zeros: false,
+    };
+
+    pub fn format(&self, time: ClockTime) -> String {
+        let (minutes, seconds, tenths) = (time.total_minutes(), time.seconds(), time.tenths());
+        if time.total_millis() < self.tenths_below_seconds.saturating_mul(MILLIS_PER_SECOND) {
+            let seconds = time.total_millis() / MILLIS_PER_SECOND;
+            if self.suppress_leading_zeros {
+                format!("{seconds}.{tenths}")
+            } else {
+                format!("{seconds:02}.{tenths}")
+            }
+        } else if self.show_hours && time.hours() > 0 {
+            format!("{}:{:02}:{seconds:02}", time.hours(), time.minutes())
+        } else if self.suppress_leading_zeros && minutes == 0 {
+            format!(":{seconds:02}")
+        } else {
+            format!("{minutes}:{seconds:02}")
+        }
+    }
+}
+
+impl Default for ClockFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proptest::prelude::*;
+    use serde_json::{json, Map, Value};
+
+    use super::{ClockFormat, ClockTime};
+    use crate::backend::mapping::transformation::{Transformation, TransformationError};
+
+    fn parse(text: &str) -> u64 {
+        ClockTime::parse(text)
+            .unwrap_or_else(|err| panic!("{err}"))
+            .unwrap_or_else(|| panic!("{text:?} parsed as blank"))
+            .total_millis()
+    }
+
+    fn transform(transformation: Transformation, text: &str) -> Value {
+        transformation
+            .transform(&json!(text), &Map::new())
+            .unwrap_or_else(|err| panic!("{transformation} of {text:?}: {err}"))
+    }
+
+    #[test]
+    fn parses_console_formats() {
+        for (text, millis) in [
+            ("12:00", 720_000),
+            ("05:00", 300_000),
+            ("5:00", 300_000),
+            ("0:45", 45_000),
+            (":45", 45_000),
+            (" 4:05.3", 245_300),
+            ("45.3", 45_300),
+            (" 5.3", 5_300),
+            ("9.95", 9_950),
+            ("45", 45_000),
+            ("1:02:03", 3_723_000),
+            ("1:02:03.4", 3_723_400),
+            (" 1: 02", 62_000),
+            ("\t12:00  ", 720_000),
+        ] {
+            assert_eq!(parse(text), millis, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn parses_blank_clocks_as_none() {
+        for text in ["", " ", "     ", "\t"] {
+            assert_eq!(ClockTime::parse(text), Ok(None), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn converts_to_and_from_durations() {
+        let time = ClockTime::parse("4:05.3").unwrap().unwrap();
+        assert_eq!(Duration::from(time), Duration::from_millis(245_300));
+        assert_eq!(ClockTime::from(Duration::from_micros(245_300_999)), time);
+        assert_eq!(ClockTime::from(Duration::MAX).total_millis(), u64::MAX);
+    }
+
+    #[test]
+    fn rejects_other_text() {
+        for text in [
+            "1:2:3:4", "1:60", "1:60:00", "a:00", "1.2.3", "-1:00", "1:0a", "12:00 AM", "4.-3",
+        ] {
+            assert!(ClockTime::parse(text).is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn time_transformations_share_the_parser() {
+        for (text, minutes, seconds, tenths, seconds_round_up) in [
+            ("4:05.3", 4, 5, 3, 6),
+            ("45.3", 0, 45, 3, 46),
+            ("59.9", 0, 59, 9, 60),
+            ("12:00", 12, 0, 0, 0),
+            (" :07", 0, 7, 0, 7),
+            ("1:02:03", 62, 3, 0, 3),
+        ] {
+            assert_eq!(transform(Transformation::TimeMinutes, text), json!(minutes));
+            assert_eq!(transform(Transformation::TimeSeconds, text), json!(seconds));
+            assert_eq!(transform(Transformation::TimeTenths, text), json!(tenths));
+            assert_eq!(
+                transform(Transformation::TimeSecondsRoundUp, text),
+                json!(seconds_round_up)
+            );
+        }
+    }
+
+    #[test]
+    fn blank_clocks_are_missing_data() {
+        for transformation in [
+            Transformation::TimeMinutes,
+            Transformation::TimeSeconds,
+            Transformation::TimeSecondsRoundUp,
+            Transformation::TimeTenths,
+            Transformation::TimeTotalMilliseconds,
+        ] {
+            assert!(matches!(
+                transformation.transform(&json!("    "), &Map::new()),
+                Err(TransformationError::UnexpectedSourceType(Value::Null))
+            ));
+        }
+        assert_eq!(
+            transform(Transformation::ClockDisplay(ClockFormat::DEFAULT), "  "),
+            json!("")
+        );
+    }
+
+    #[test]
+    fn every_time_transformation_can_be_picked() {
+        let all = Transformation::ALL;
+        for transformation in [
+            Transformation::TimeMinutes,
+            Transformation::TimeSeconds,
+            Transformation::TimeSecondsRoundUp,
+            Transformation::TimeTenths,
+            Transformation::TimeTotalMilliseconds,
+        ] {
+            assert!(all.contains(&transformation), "{transformation}");
+        }
+    }
+
+    #[test]
+    fn formats_for_display() {
+        let hours = ClockFormat {
+            show_hours: true,
+            ..ClockFormat::DEFAULT
+        };
+        let no_zeros = ClockFormat {
+            suppress_leading_zeros: true,
+            ..ClockFormat::DEFAULT
+        };
+        let no_tenths = ClockFormat {
+            tenths_below_seconds: 0,
+            ..no_zeros
+        };
+        for (format, text, display) in [
+            (ClockFormat::DEFAULT, "12:00", "12:00"),
+            (ClockFormat::DEFAULT, "1:00", "1:00"),
+            (ClockFormat::DEFAULT, "0:59.9", "59.9"),
+            (ClockFormat::DEFAULT, "5.3", "05.3"),
+            (ClockFormat::DEFAULT, "1:15:00", "75:00"),
+            (hours, "1:15:00", "1:15:00"),
+            (hours, "59:59", "59:59"),
+            (no_zeros, "5.3", "5.3"),
+            (no_tenths, "0:45.3", ":45"),
+            (no_tenths, "10:00", "10:00"),
+        ] {
+            assert_eq!(
+                format.format(ClockTime::parse(text).unwrap().unwrap()),
+                display
+            );
+        }
+    }
+
+    /// Clock text with every combination of components and padding.
+    fn clock_text() -> impl Strategy<Value = (String, u64)> {
+        (
+            0..100u64,
+            0..60u64,
+            0..60u64,
+            proptest::option::of(0..10u64),
+            0..3usize,
+            " {0,3}",
+            " {0,3}",
+        )
+            .prop_map(
+                |(hours, minutes, seconds, tenths, components, before, after)| {
+                    let tenths_text = tenths
+                        .map(|tenths| format!(".{tenths}"))
+                        .unwrap_or_default();
+                    let (text, millis) = match components {
+                        0 => (format!("{seconds}{tenths_text}"), seconds * 1000),
+                        1 => (
+                            format!("{minutes}:{seconds:02}{tenths_text}"),
+                            (minutes * 60 + seconds) * 1000,
+                        ),
+                        _ => (
+                            format!("{hours}:{minutes:02}:{seconds:02}{tenths_text}"),
+                            ((hours * 60 + minutes) * 60 + seconds) * 1000,
+                        ),
+                    };
+                    (
+                        format!("{before}{text}{after}"),
+                        millis + tenths.unwrap_or_default() * 100,
+                    )
+                },
+            )
+    }
+
+    fn clock_format() -> impl Strategy<Value = ClockFormat> {
+        (0..600u64, any::<bool>(), any::<bool>()).prop_map(
+            |(tenths_below_seconds, show_hours, suppress_leading_zeros)| ClockFormat {
+                tenths_below_seconds,
+                show_hours,
+                suppress_leading_zeros,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn parses_every_clock_text((text, millis) in clock_text()) {
+            prop_assert_eq!(ClockTime::parse(&text), Ok(Some(ClockTime { millis })));
+        }
+
+        #[test]
+        fn never_panics_on_console_text(text in "[0-9:. ]{0,16}") {
+            let _ = ClockTime::parse(&text);
+        }
+
+        #[test]
+        fn never_panics_on_any_text(text in any::<String>()) {
+            let _ = ClockTime::parse(&text);
+        }
+
+        #[test]
+        fn components_add_up((text, millis) in clock_text()) {
+            let minutes = transform(Transformation::TimeMinutes, &text).as_u64().unwrap();
+            let seconds = transform(Transformation::TimeSeconds, &text).as_u64().unwrap();
+            let tenths = transform(Transformation::TimeTenths, &text).as_u64().unwrap();
+            prop_assert_eq!(minutes * 60_000 + seconds * 1000 + tenths * 100, millis);
+            prop_assert_eq!(
+                transform(Transformation::TimeTotalMilliseconds, &text),
+                json!(millis)
+            );
+        }
+
+        #[test]
+        fn rounding_up_adds_a_second_for_tenths((text, millis) in clock_text()) {
+            let seconds = transform(Transformation::TimeSeconds, &text).as_u64().unwrap();
+            let rounded = transform(Transformation::TimeSecondsRoundUp, &text).as_u64().unwrap();
+            prop_assert_eq!(rounded, seconds + u64::from(millis % 1000 != 0));
+        }
+
+        #[test]
+        fn display_parses_back(millis in 0..360_000_000u64, format in clock_format()) {
+            let time = ClockTime { millis };
+            let display = format.format(time);
+            // the display only has as much precision as it shows
+            let precision = if display.contains('.') { 100 } else { 1000 };
+            prop_assert_eq!(parse(&display), millis / precision * precision, "{}", display);
+        }
+    }
+}
@@ -0,0 +1,794 @@
+//! A small expression language for fields computed from the whole source map,
+//! e.g. `home_team_score - guest_team_score` or
+//! `period > 4 ? "OT" : period`.
+//!
+//! Source fields are referred to by name (or with `field("name")` for names
+//! that aren't identifiers) and `value` is the mapping item's own source
+//! value. There are numbers, strings, `true`, `false` and `null`, arithmetic
+//! (`+ - * / %`), comparisons (`== != < <= > >=`), `&&`, `||`, `!`,
+//! conditionals (`cond ? then : else`) and a few functions, see
+//! [`Function`]. `+` adds when both sides are numbers (or strings holding
+//! numbers, like most console fields) and concatenates otherwise. That goes
+//! for string literals too, so `"1" + "2"` is `3`; a template (see
+//! [`super::template`]) joins text that might look like numbers.
+//!
+//! Expressions can only compute a value from the source map: there are no
+//! loops, assignments or side effects, and nesting (including long chains of
+//! operators) is limited so a profile can't overflow the stack.
+
+use std::{error::Error, fmt::Display};
+
+use serde_json::{Map, Number, Value};
+
+use super::parsed::Parse;
+
+/// How deeply expressions may nest, counting each operator in a chain.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Identifier(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+    Comma,
+    Question,
+    Colon,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "{}", number),
+            Token::String(string) => write!(f, "{:?}", string),
+            Token::Identifier(identifier) => f.write_str(identifier),
+            Token::Operator(operator) => f.write_str(operator),
+            Token::LeftParen => f.write_str("("),
+            Token::RightParen => f.write_str(")"),
+            Token::Comma => f.write_str(","),
+            Token::Question => f.write_str("?"),
+            Token::Colon => f.write_str(":"),
+        }
+    }
+}
+
+/// Operators, longest first so `<=` isn't read as `<`.
+const OPERATORS: [&str; 14] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Where the error is, in characters from the start of the expression
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at character {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let current = chars[i];
+        let token = if current.is_whitespace() {
+            i += 1;
+            continue;
+        } else if current.is_ascii_digit()
+            || (current == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text = chars[start..i].iter().collect::<String>();
+            Token::Number(text.parse().map_err(|_| ParseError {
+                position: start,
+                message: format!("{} isn't a number", text),
+            })?)
+        } else if current == '"' || current == '\'' {
+            i += 1;
+            let mut string = String::new();
+            loop {
+                match chars.get(i) {
+                    None => {
+                        return Err(ParseError {
+                            position: start,
+                            message: "the string is never closed".to_owned(),
+                        })
+                    }
+                    Some(c) if *c == current => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        string.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(c) => {
+                        string.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            Token::String(string)
+        } else if current.is_alphabetic() || current == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            Token::Identifier(chars[start..i].iter().collect())
+        } else {
+            i += 1;
+            match current {
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                ',' => Token::Comma,
+                '?' => Token::Question,
+                ':' => Token::Colon,
+                _ => {
+                    let rest = chars[start..].iter().take(2).collect::<String>();
+                    let operator = OPERATORS
+                        .into_iter()
+                        .find(|operator| rest.starts_with(operator))
+                        .ok_or_else(|| ParseError {
+                            position: start,
+                            message: format!("unexpected character {:?}", current),
+                        })?;
+                    i = start + operator.len();
+                    Token::Operator(operator)
+                }
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// The functions expressions can call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// `field(name)`: the source field called `name`
+    Field,
+    /// `number(x)`: `x` as a number
+    Number,
+    /// `string(x)`: `x` as a string
+    String,
+    /// `trim(x)`: `x` as a string without surrounding spaces
+    Trim,
+    /// `len(x)`: the number of characters in `x`
+    Len,
+    Abs,
+    Round,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "field" => Function::Field,
+            "number" => Function::Number,
+            "string" => Function::String,
+            "trim" => Function::Trim,
+            "len" => Function::Len,
+            "abs" => Function::Abs,
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn from_operator(operator: &str) -> Option<Self> {
+        Some(match operator {
+            "+" => BinaryOperator::Add,
+            "-" => BinaryOperator::Subtract,
+            "*" => BinaryOperator::Multiply,
+            "/" => BinaryOperator::Divide,
+            "%" => BinaryOperator::Remainder,
+            "==" => BinaryOperator::Equal,
+            "!=" => BinaryOperator::NotEqual,
+            "<" => BinaryOperator::Less,
+            "<=" => BinaryOperator::LessOrEqual,
+            ">" => BinaryOperator::Greater,
+            ">=" => BinaryOperator::GreaterOrEqual,
+            "&&" => BinaryOperator::And,
+            "||" => BinaryOperator::Or,
+            _ => return None,
+        })
+    }
+
+    /// Operators binding tighter have higher precedence.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 3,
+            BinaryOperator::Less
+            | BinaryOperator::LessOrEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterOrEqual => 4,
+            BinaryOperator::Add | BinaryOperator::Subtract => 5,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Remainder => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    /// The mapping item's source value
+    Value,
+    Field(String),
+    Negate(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Where the expression ends, for errors about running out of tokens
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.end, |(position, _)| *position)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            position: self.position(),
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(token) if *token == expected => {
+                self.next += 1;
+                Ok(())
+            }
+            Some(token) => Err(self.error(format!("expected {} but found {}", expected, token))),
+            None => Err(self.error(format!("expected {} but the expression ended", expected))),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            Err(self.error("the expression is nested too deeply"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, ParseError> {
+        self.enter()?;
+        let condition = self.binary(0)?;
+        let node = if self.peek() == Some(&Token::Question) {
+            self.next += 1;
+            let then = self.expression()?;
+            self.expect(Token::Colon)?;
+            let otherwise = self.expression()?;
+            Node::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise))
+        } else {
+            condition
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    /// Parses operators binding tighter than `min_precedence`.
+    fn binary(&mut self, min_precedence: u8) -> Result<Node, ParseError> {
+        let mut left = self.unary()?;
+        // each operator nests `left` a level deeper, so a long chain like
+        // `1 + 1 + 1 ...` counts towards the limit too
+        let mut folded = 0;
+        while let Some(Token::Operator(operator)) = self.peek() {
+            let Some(operator) = BinaryOperator::from_operator(operator) else {
+                break;
+            };
+            if operator.precedence() <= min_precedence {
+                break;
+            }
+            self.enter()?;
+            folded += 1;
+            self.next += 1;
+            let right = self.binary(operator.precedence())?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+        self.depth -= folded;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        self.enter()?;
+        let node = match self.peek() {
+            Some(Token::Operator("-")) => {
+                self.next += 1;
+                Node::Negate(Box::new(self.unary()?))
+            }
+            Some(Token::Operator("!")) => {
+                self.next += 1;
+                Node::Not(Box::new(self.unary()?))
+            }
+            _ => self.primary()?,
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, ParseError> {
+        let Some((position, token)) = self.tokens.get(self.next).cloned() else {
+            return Err(self.error("expected a value but the expression ended"));
+        };
+        self.next += 1;
+        match token {
+            Token::Number(number) => Ok(Node::Literal(
+                number_value(number).ok_or_else(|| self.error("the number is too large"))?,
+            )),
+            Token::String(string) => Ok(Node::Literal(Value::String(string))),
+            Token::LeftParen => {
+                let node = self.expression()?;
+                self.expect(Token::RightParen)?;
+                Ok(node)
+            }
+            Token::Identifier(identifier) if self.peek() == Some(&Token::LeftParen) => {
+                self.next -= 1;
+                let function = Function::from_name(&identifier).ok_or_else(|| {
+                    self.error(format!("there's no function called {}", identifier))
+                })?;
+                self.next += 2;
+                let mut arguments = Vec::new();
+                if self.peek() != Some(&Token::RightParen) {
+                    loop {
+                        arguments.push(self.expression()?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.next += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                if arguments.len() != function.arity() {
+                    // at the function's name, since the call is over
+                    return Err(ParseError {
+                        position,
+                        message: format!(
+                            "{} takes {} argument(s) but was given {}",
+                            identifier,
+                            function.arity(),
+                            arguments.len()
+                        ),
+                    });
+                }
+                Ok(Node::Call(function, arguments))
+            }
+            Token::Identifier(identifier) => Ok(match identifier.as_str() {
+                "true" => Node::Literal(Value::Bool(true)),
+                "false" => Node::Literal(Value::Bool(false)),
+                "null" => Node::Literal(Value::Null),
+                "value" => Node::Value,
+                _ => Node::Field(identifier),
+            }),
+            token => {
+                self.next -= 1;
+                Err(self.error(format!("expected a value but found {}", token)))
+            }
+        }
+    }
+}
+
+/// A parsed expression, ready to evaluate against source maps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression(Node);
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            end: source.chars().count(),
+            depth: 0,
+        };
+        if parser.peek().is_none() {
+            return Err(parser.error("the expression is empty"));
+        }
+        let node = parser.expression()?;
+        match parser.peek() {
+            Some(token) => Err(parser.error(format!("unexpected {} after the expression", token))),
+            None => Ok(Self(node)),
+        }
+    }
+
+    /// Evaluates the expression with `value` as the mapping item's source
+    /// value and `source` as the whole source map.
+    pub fn evaluate(&self, value: &Value, source: &Map<String, Value>) -> Result<Value, EvalError> {
+        evaluate(&self.0, value, source)
+    }
+}
+
+impl Parse for Expression {
+    type Error = ParseError;
+
+    fn parse(source: &str) -> Result<Self, ParseError> {
+        Expression::parse(source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// A null value was used where it can't be, usually because the console
+    /// hasn't sent the field yet
+    Null,
+    UnknownField(String),
+    NotANumber(Value),
+    DivisionByZero,
+    /// The result of arithmetic is too large, or otherwise can't be output as
+    /// a JSON number
+    NonFinite,
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Null => write!(f, "a null value was used in the expression"),
+            EvalError::UnknownField(field) => write!(f, "source field {} does not exist", field),
+            EvalError::NotANumber(value) => write!(f, "{} is not a number", value),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::NonFinite => write!(f, "the result is too large to be a number"),
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+/// Integers stay integers so they print without a decimal point.
+fn number_value(number: f64) -> Option<Value> {
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        Some(Value::Number(Number::from(number as i64)))
+    } else {
+        Number::from_f64(number).map(Value::Number)
+    }
+}
+
+/// The value as a number, if it's a number or a string holding one.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_number(value: &Value) -> Result<f64, EvalError> {
+    match value {
+        Value::Null => Err(EvalError::Null),
+        value => as_number(value).ok_or_else(|| EvalError::NotANumber(value.clone())),
+    }
+}
+
+fn to_text(value: &Value) -> Result<String, EvalError> {
+    match value {
+        Value::Null => Err(EvalError::Null),
+        Value::String(string) => Ok(string.clone()),
+        value => Ok(value.to_string()),
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(bool) => *bool,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(string) => !string.trim().is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+fn field(name: &str, source: &Map<String, Value>) -> Result<Value, EvalError> {
+    source
+        .get(name)
+        .cloned()
+        .ok_or_else(|| EvalError::UnknownField(name.to_owned()))
+}
+
+fn evaluate(node: &Node, value: &Value, source: &Map<String, Value>) -> Result<Value, EvalError> {
+    let number = |number: f64| number_value(number).ok_or(EvalError::NonFinite);
+    match node {
+        Node::Literal(literal) => Ok(literal.clone()),
+        Node::Value => Ok(value.clone()),
+        Node::Field(name) => field(name, source),
+        Node::Negate(node) => number(-to_number(&evaluate(node, value, source)?)?),
+        Node::Not(node) => Ok(Value::Bool(!is_truthy(&evaluate(node, value, source)?))),
+        Node::Conditional(condition, then, otherwise) => {
+            if is_truthy(&evaluate(condition, value, source)?) {
+                evaluate(then, value, source)
+            } else {
+                evaluate(otherwise, value, source)
+            }
+        }
+        Node::Binary(BinaryOperator::And, left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, value, source)?)
+                && is_truthy(&evaluate(right, value, source)?),
+        )),
+        Node::Binary(BinaryOperator::Or, left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, value, source)?)
+                || is_truthy(&evaluate(right, value, source)?),
+        )),
+        Node::Binary(operator, left, right) => {
+            let left = evaluate(left, value, source)?;
+            let right = evaluate(right, value, source)?;
+            let numbers = as_number(&left).zip(as_number(&right));
+            match operator {
+                BinaryOperator::Add => match numbers {
+                    Some((left, right)) => number(left + right),
+                    None => Ok(Value::String(to_text(&left)? + &to_text(&right)?)),
+                },
+                BinaryOperator::Subtract => number(to_number(&left)? - to_number(&right)?),
+                BinaryOperator::Multiply => number(to_number(&left)? * to_number(&right)?),
+                BinaryOperator::Divide | BinaryOperator::Remainder => {
+                    let (left, right) = (to_number(&left)?, to_number(&right)?);
+                    if right == 0.0 {
+                        return Err(EvalError::DivisionByZero);
+                    }
+                    number(if *operator == BinaryOperator::Divide {
+                        left / right
+                    } else {
+                        left % right
+                    })
+                }
+                BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                    let equal = match (&left, &right, numbers) {
+                        (Value::Null, _, _) | (_, Value::Null, _) => left == right,
+                        (_, _, Some((left, right))) => left == right,
+                        _ => to_text(&left)? == to_text(&right)?,
+                    };
+                    Ok(Value::Bool(equal == (*operator == BinaryOperator::Equal)))
+                }
+                _ => {
+                    let ordering = match numbers {
+                        Some((left, right)) => left.total_cmp(&right),
+                        None => to_text(&left)?.cmp(&to_text(&right)?),
+                    };
+                    Ok(Value::Bool(match operator {
+                        BinaryOperator::Less => ordering.is_lt(),
+                        BinaryOperator::LessOrEqual => ordering.is_le(),
+                        BinaryOperator::Greater => ordering.is_gt(),
+                        _ => ordering.is_ge(),
+                    }))
+                }
+            }
+        }
+        Node::Call(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate(argument, value, source))
+                .collect::<Result<Vec<_>, _>>()?;
+            match function {
+                Function::Field => field(&to_text(&arguments[0])?, source),
+                Function::Number => number(to_number(&arguments[0])?),
+                Function::String => Ok(Value::String(to_text(&arguments[0])?)),
+                Function::Trim => Ok(Value::String(to_text(&arguments[0])?.trim().to_owned())),
+                Function::Len => Ok(Value::from(to_text(&arguments[0])?.chars().count())),
+                Function::Abs => number(to_number(&arguments[0])?.abs()),
+                Function::Round => number(to_number(&arguments[0])?.round()),
+                Function::Floor => number(to_number(&arguments[0])?.floor()),
+                Function::Ceil => number(to_number(&arguments[0])?.ceil()),
+                Function::Min => number(to_number(&arguments[0])?.min(to_number(&arguments[1])?)),
+                Function::Max => number(to_number(&arguments[0])?.max(to_number(&arguments[1])?)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{EvalError, Expression, ParseError, MAX_DEPTH};
+
+    fn evaluate(source: &str) -> Result<Value, EvalError> {
+        let fields = json!({
+            "home_team_score": " 12",
+            "guest_team_score": "7",
+            "period": 4,
+            "team name": "Hawks",
+            "clock": null,
+        });
+        Expression::parse(source)
+            .unwrap_or_else(|err| panic!("{source:?}: {err}"))
+            .evaluate(&json!("5"), fields.as_object().unwrap())
+    }
+
+    fn value(source: &str) -> Value {
+        evaluate(source).unwrap_or_else(|err| panic!("{source:?}: {err}"))
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        Expression::parse(source).expect_err(source)
+    }
+
+    #[test]
+    fn follows_precedence() {
+        for (source, expected) in [
+            ("1 + 2 * 3", json!(7)),
+            ("(1 + 2) * 3", json!(9)),
+            ("10 - 4 - 3", json!(3)),
+            ("12 / 2 / 3", json!(2)),
+            ("7 % 4 * 2", json!(6)),
+            ("1 + 1 == 2", json!(true)),
+            ("1 < 2 == 2 < 1", json!(false)),
+            ("true || false && false", json!(true)),
+            ("period > 4 ? \"OT\" : period", json!(4)),
+            ("1 ? 2 : 3 ? 4 : 5", json!(2)),
+        ] {
+            assert_eq!(value(source), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn negates_and_inverts() {
+        for (source, expected) in [
+            ("-2 * 3", json!(-6)),
+            ("--2", json!(2)),
+            ("2 - -1", json!(3)),
+            ("-value", json!(-5)),
+            ("-guest_team_score + 1", json!(-6)),
+            ("!0", json!(true)),
+            ("!!period", json!(true)),
+            ("-1.5", json!(-1.5)),
+        ] {
+            assert_eq!(value(source), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn adds_numeric_text_and_joins_other_text() {
+        for (source, expected) in [
+            ("home_team_score - guest_team_score", json!(5)),
+            ("home_team_score + 1", json!(13)),
+            ("\"1\" + \"2\"", json!(3)),
+            ("\"Q\" + period", json!("Q4")),
+            ("field(\"team name\") + \" win\"", json!("Hawks win")),
+            ("\"a\" + 1", json!("a1")),
+        ] {
+            assert_eq!(value(source), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn reports_evaluation_errors() {
+        for (source, expected) in [
+            ("1 / 0", EvalError::DivisionByZero),
+            ("5 % (2 - 2)", EvalError::DivisionByZero),
+            ("clock + 1", EvalError::Null),
+            ("-clock", EvalError::Null),
+            (
+                "shot_clock",
+                EvalError::UnknownField("shot_clock".to_owned()),
+            ),
+            (
+                "field(\"shot clock\")",
+                EvalError::UnknownField("shot clock".to_owned()),
+            ),
+            ("\"Hawks\" - 1", EvalError::NotANumber(json!("Hawks"))),
+        ] {
+            assert_eq!(evaluate(source), Err(expected), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_results_that_arent_finite() {
+        let huge = "9".repeat(200);
+        assert_eq!(
+            evaluate(&format!("{huge} * {huge}")),
+            Err(EvalError::NonFinite)
+        );
+        assert_eq!(
+            evaluate(&format!("-{huge} * {huge}")),
+            Err(EvalError::NonFinite)
+        );
+        assert_eq!(
+            EvalError::NonFinite.to_string(),
+            "the result is too large to be a number"
+        );
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(value(&nested(MAX_DEPTH / 4)), json!(1));
+        assert!(parse_error(&nested(MAX_DEPTH))
+            .message
+            .contains("nested too deeply"));
+        assert!(parse_error(&format!("{}1", "-".repeat(MAX_DEPTH + 1)))
+            .message
+            .contains("nested too deeply"));
+    }
+
+    #[test]
+    fn limits_long_chains() {
+        let chain = |length: usize| format!("1{}", " + 1".repeat(length));
+        assert_eq!(value(&chain(MAX_DEPTH / 4)), json!(MAX_DEPTH / 4 + 1));
+        assert!(parse_error(&chain(MAX_DEPTH))
+            .message
+            .contains("nested too deeply"));
+        assert!(parse_error(&chain(200_000))
+            .message
+            .contains("nested too deeply"));
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        for (source, position) in [
+            ("", 0),
+            ("1 +", 3),
+            ("1 $ 2", 2),
+            ("(1 + 2", 6),
+            ("1 2", 2),
+            ("\"never closed", 0),
+            ("period > 4 ? 1", 14),
+            ("foo(1)", 0),
+            ("min(1)", 0),
+            ("1 + )", 4),
+        ] {
+            assert_eq!(parse_error(source).position, position, "{source:?}");
+        }
+        assert_eq!(
+            parse_error("1 +").to_string(),
+            "at character 4: expected a value but the expression ended"
+        );
+    }
+}
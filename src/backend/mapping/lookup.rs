@@ -0,0 +1,143 @@
+//! Tables translating console codes into display text, e.g. period `5` to
+//! `OT` or possession `H` to the home team's name.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transformation::TransformationError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// What to do with a value that isn't in the table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LookupFallback {
+    #[default]
+    Fail,
+    Default(String),
+    PassThrough,
+}
+
+impl LookupFallback {
+    pub const ALL: [LookupFallback; 3] = [
+        LookupFallback::Fail,
+        LookupFallback::Default(String::new()),
+        LookupFallback::PassThrough,
+    ];
+}
+
+impl Display for LookupFallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LookupFallback::Fail => "Fail if not in the table",
+            LookupFallback::Default(_) => "Use a default if not in the table",
+            LookupFallback::PassThrough => "Pass through if not in the table",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupTable {
+    pub entries: Vec<LookupEntry>,
+    pub fallback: LookupFallback,
+}
+
+impl LookupTable {
+    pub const EMPTY: LookupTable = LookupTable {
+        entries: Vec::new(),
+        fallback: LookupFallback::Fail,
+    };
+
+    /// Looks up the value by its text, ignoring the padding the console adds.
+    pub fn lookup(&self, value: &Value) -> Result<Value, TransformationError> {
+        let key = match value {
+            Value::Null => return Err(TransformationError::UnexpectedSourceType(Value::Null)),
+            Value::String(string) => string.trim().to_owned(),
+            Value::Number(_) | Value::Bool(_) => value.to_string(),
+            _ => return Err(TransformationError::UnexpectedSourceType(value.clone())),
+        };
+        match self.entries.iter().find(|entry| entry.key.trim() == key) {
+            Some(entry) => Ok(Value::String(entry.value.clone())),
+            None => match &self.fallback {
+                LookupFallback::Fail => Err(TransformationError::NoLookupMatch(key)),
+                LookupFallback::Default(default) => Ok(Value::String(default.clone())),
+                LookupFallback::PassThrough => Ok(value.clone()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{LookupEntry, LookupFallback, LookupTable};
+    use crate::backend::mapping::transformation::TransformationError;
+
+    fn periods(fallback: LookupFallback) -> LookupTable {
+        LookupTable {
+            entries: [("5", "OT"), (" H ", "Home"), ("true", "Yes")]
+                .into_iter()
+                .map(|(key, value)| LookupEntry {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                })
+                .collect(),
+            fallback,
+        }
+    }
+
+    #[test]
+    fn matches_keys_ignoring_padding() {
+        let table = periods(LookupFallback::Fail);
+        for (value, expected) in [
+            (json!("5"), "OT"),
+            (json!(" 5"), "OT"),
+            (json!("H"), "Home"),
+            (json!("H  "), "Home"),
+            (json!(5), "OT"),
+            (json!(true), "Yes"),
+        ] {
+            assert_eq!(table.lookup(&value).unwrap(), json!(expected), "{value}");
+        }
+    }
+
+    #[test]
+    fn falls_back_for_values_not_in_the_table() {
+        assert!(matches!(
+            periods(LookupFallback::Fail).lookup(&json!(" 4")),
+            Err(TransformationError::NoLookupMatch(key)) if key == "4"
+        ));
+        assert_eq!(
+            periods(LookupFallback::Default("--".to_owned()))
+                .lookup(&json!("4"))
+                .unwrap(),
+            json!("--")
+        );
+        for value in [json!(" 4"), json!(4)] {
+            assert_eq!(
+                periods(LookupFallback::PassThrough).lookup(&value).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn doesnt_look_up_missing_or_structured_values() {
+        let table = periods(LookupFallback::PassThrough);
+        for value in [Value::Null, json!(["5"]), json!({ "period": "5" })] {
+            assert!(
+                matches!(
+                    table.lookup(&value),
+                    Err(TransformationError::UnexpectedSourceType(ref unexpected)) if *unexpected == value
+                ),
+                "{value}"
+            );
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Text in a profile that's parsed before it's used, like an expression. It's
+//! parsed the first time it's needed and kept, so mapping a payload doesn't
+//! parse it again. Editing the text makes a new [`Parsed`], which is parsed
+//! afresh.
+
+use std::{fmt::Debug, sync::OnceLock};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub trait Parse: Sized {
+    type Error;
+
+    fn parse(source: &str) -> Result<Self, Self::Error>;
+}
+
+/// The source text and what it parses to. It's saved as just the text.
+pub struct Parsed<T: Parse> {
+    source: String,
+    parsed: OnceLock<Result<T, T::Error>>,
+}
+
+impl<T: Parse> Parsed<T> {
+    // each use of the constant is a copy with nothing parsed yet
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const EMPTY: Self = Parsed {
+        source: String::new(),
+        parsed: OnceLock::new(),
+    };
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// What the text parses to, parsing it if this is the first time.
+    pub fn parsed(&self) -> Result<&T, &T::Error> {
+        self.parsed.get_or_init(|| T::parse(&self.source)).as_ref()
+    }
+}
+
+impl<T: Parse> From<String> for Parsed<T> {
+    fn from(source: String) -> Self {
+        Parsed {
+            source,
+            parsed: OnceLock::new(),
+        }
+    }
+}
+
+impl<T: Parse> Default for Parsed<T> {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl<T: Parse> Clone for Parsed<T>
+where
+    T: Clone,
+    T::Error: Clone,
+{
+    fn clone(&self) -> Self {
+        Parsed {
+            source: self.source.clone(),
+            parsed: self.parsed.clone(),
+        }
+    }
+}
+
+impl<T: Parse> Debug for Parsed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+/// Parsing the same text always gives the same result, so only the text is
+/// compared.
+impl<T: Parse> PartialEq for Parsed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl<T: Parse> Eq for Parsed<T> {}
+
+impl<T: Parse> Serialize for Parsed<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de, T: Parse> Deserialize<'de> for Parsed<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{Parse, Parsed};
+
+    thread_local! {
+        // per thread, since tests run at the same time
+        static PARSES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts how many times it's parsed
+    #[derive(Debug, Clone, PartialEq)]
+    struct Counted(usize);
+
+    impl Parse for Counted {
+        type Error = String;
+
+        fn parse(source: &str) -> Result<Self, String> {
+            PARSES.set(PARSES.get() + 1);
+            source
+                .parse()
+                .map(Counted)
+                .map_err(|_| format!("{source} isn't a number"))
+        }
+    }
+
+    #[test]
+    fn parses_once_and_keeps_the_result() {
+        let parsed = Parsed::<Counted>::from("12".to_owned());
+        let before = PARSES.get();
+        assert_eq!(parsed.parsed(), Ok(&Counted(12)));
+        assert_eq!(parsed.parsed(), Ok(&Counted(12)));
+        // clones share the result rather than parsing again
+        assert_eq!(parsed.clone().parsed(), Ok(&Counted(12)));
+        assert_eq!(PARSES.get(), before + 1);
+
+        let failed = Parsed::<Counted>::from("twelve".to_owned());
+        assert_eq!(failed.parsed(), Err(&"twelve isn't a number".to_owned()));
+        assert_eq!(failed.source(), "twelve");
+    }
+
+    #[test]
+    fn saves_as_the_source_text() {
+        let parsed = Parsed::<Counted>::from("12".to_owned());
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), r#""12""#);
+        let loaded: Parsed<Counted> = serde_json::from_str(r#""12""#).unwrap();
+        assert_eq!(loaded, parsed);
+        assert_eq!(loaded.parsed(), Ok(&Counted(12)));
+    }
+}
@@ -0,0 +1,291 @@
+//! Destination paths like `home.fouls` or `periods[2].home`, which build up
+//! objects and arrays in the output across mapping items. A name without `.`
+//! or `[` is a plain field, like before paths. A `\` makes the character
+//! after it part of the name, e.g. `score\.home` is the field `score.home`.
+//!
+//! Profiles saved before paths existed have their destination fields escaped
+//! when they're imported, so their names stay plain fields (see
+//! [`crate::backend::profile::ProfileV2::destination_paths`]).
+
+use std::{error::Error, fmt::Display};
+
+use serde_json::{Map, Value};
+
+/// Indexes are filled in from 0, so a typo shouldn't make a huge array.
+const MAX_INDEX: usize = 9999;
+
+/// The characters that make a name a path, unless they're escaped.
+const SPECIAL_CHARS: [char; 4] = ['.', '[', ']', '\\'];
+
+/// Escapes `name` so it's read as a single field, even with `.`, `[`, `]` or
+/// `\` in it.
+pub fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for char in name.chars() {
+        if SPECIAL_CHARS.contains(&char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationPath {
+    /// Starts with a key, since the output is an object
+    segments: Vec<PathSegment>,
+}
+
+impl DestinationPath {
+    pub fn parse(path: &str) -> Result<Self, PathError> {
+        if !path.contains(SPECIAL_CHARS) {
+            return Ok(DestinationPath {
+                segments: vec![PathSegment::Key(path.to_owned())],
+            });
+        }
+        let mut segments = Vec::new();
+        let mut chars = path.chars().peekable();
+        loop {
+            // a key runs up to the next separator that isn't escaped
+            let mut key = String::new();
+            while let Some(char) = chars.next_if(|char| !matches!(char, '.' | '[' | ']')) {
+                if char == '\\' {
+                    key.push(chars.next().ok_or_else(|| {
+                        PathError::new(path, "a `\\` at the end doesn't escape anything")
+                    })?);
+                } else {
+                    key.push(char);
+                }
+            }
+            if key.is_empty() {
+                return Err(PathError::new(path, "a field name is empty"));
+            }
+            segments.push(PathSegment::Key(key));
+            while chars.next_if_eq(&'[').is_some() {
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(char) => index.push(char),
+                        None => return Err(PathError::new(path, "a `[` isn't closed")),
+                    }
+                }
+                if index.is_empty() || !index.chars().all(|char| char.is_ascii_digit()) {
+                    return Err(PathError::new(path, "an index isn't a whole number"));
+                }
+                let index = index
+                    .parse()
+                    .ok()
+                    .filter(|index| *index <= MAX_INDEX)
+                    .ok_or_else(|| PathError::new(path, "an index is over 9999"))?;
+                segments.push(PathSegment::Index(index));
+            }
+            match chars.next() {
+                None => return Ok(DestinationPath { segments }),
+                Some('.') => {}
+                Some(']') => return Err(PathError::new(path, "a `]` isn't opened")),
+                Some(_) => {
+                    return Err(PathError::new(
+                        path,
+                        "an index must be followed by `.`, `[` or the end",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Whether the paths are the same or one is inside the other, so they'd
+    /// write over each other.
+    pub fn overlaps(&self, other: &DestinationPath) -> bool {
+        self.segments
+            .iter()
+            .zip(&other.segments)
+            .all(|(segment, other)| segment == other)
+    }
+
+    /// Inserts `value` at the path, making the objects and arrays on the way
+    /// and filling arrays up to the index with nulls. Returns `false` if a
+    /// value on the way is the wrong kind, e.g. `periods[0]` and then
+    /// `periods.home`.
+    pub fn insert(&self, destination: &mut Map<String, Value>, value: Value) -> bool {
+        let Some((PathSegment::Key(first), rest)) = self.segments.split_first() else {
+            return false;
+        };
+        let mut slot = destination.entry(first.clone()).or_insert(Value::Null);
+        for segment in rest {
+            slot = match child(slot, segment) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        *slot = value;
+        true
+    }
+
+    /// The value at the path, if there's one there.
+    pub fn get<'v>(&self, source: &'v Map<String, Value>) -> Option<&'v Value> {
+        let Some((PathSegment::Key(first), rest)) = self.segments.split_first() else {
+            return None;
+        };
+        rest.iter()
+            .try_fold(source.get(first)?, |value, segment| match segment {
+                PathSegment::Key(key) => value.get(key),
+                PathSegment::Index(index) => value.get(index),
+            })
+    }
+}
+
+fn child<'a>(slot: &'a mut Value, segment: &PathSegment) -> Option<&'a mut Value> {
+    match segment {
+        PathSegment::Key(key) => {
+            if slot.is_null() {
+                *slot = Value::Object(Map::new());
+            }
+            slot.as_object_mut()
+                .map(|object| object.entry(key.clone()).or_insert(Value::Null))
+        }
+        PathSegment::Index(index) => {
+            if slot.is_null() {
+                *slot = Value::Array(Vec::new());
+            }
+            slot.as_array_mut().map(|array| {
+                if array.len() <= *index {
+                    array.resize(index + 1, Value::Null);
+                }
+                &mut array[*index]
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    pub path: String,
+    pub message: &'static str,
+}
+
+impl PathError {
+    fn new(path: &str, message: &'static str) -> Self {
+        PathError {
+            path: path.to_owned(),
+            message,
+        }
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid destination path {:?}: {}",
+            self.path, self.message
+        )
+    }
+}
+
+impl Error for PathError {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn keys(keys: &[&str]) -> DestinationPath {
+        DestinationPath {
+            segments: keys
+                .iter()
+                .map(|key| PathSegment::Key((*key).to_owned()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn splits_keys_and_indexes() {
+        assert_eq!(
+            DestinationPath::parse("periods[2].home").unwrap().segments,
+            vec![
+                PathSegment::Key("periods".to_owned()),
+                PathSegment::Index(2),
+                PathSegment::Key("home".to_owned()),
+            ]
+        );
+        assert_eq!(DestinationPath::parse("home").unwrap(), keys(&["home"]));
+    }
+
+    #[test]
+    fn escaped_characters_are_part_of_the_name() {
+        assert_eq!(
+            DestinationPath::parse(r"score\.home.fouls\[1\]").unwrap(),
+            keys(&["score.home", "fouls[1]"])
+        );
+        assert_eq!(
+            DestinationPath::parse(r"back\\slash").unwrap(),
+            keys(&[r"back\slash"])
+        );
+    }
+
+    #[test]
+    fn escaping_keeps_a_name_as_one_field() {
+        for name in ["score.home", "periods[2]", r"a\b", "home"] {
+            assert_eq!(
+                DestinationPath::parse(&escape(name)).unwrap(),
+                keys(&[name])
+            );
+        }
+    }
+
+    #[test]
+    fn reports_invalid_paths() {
+        for (path, message) in [
+            ("score.", "a field name is empty"),
+            ("periods[2", "a `[` isn't closed"),
+            ("periods[two]", "an index isn't a whole number"),
+            ("periods[10000]", "an index is over 9999"),
+            ("periods]", "a `]` isn't opened"),
+            (
+                "periods[2]home",
+                "an index must be followed by `.`, `[` or the end",
+            ),
+            (r"home\", "a `\\` at the end doesn't escape anything"),
+        ] {
+            assert_eq!(DestinationPath::parse(path).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn gets_what_was_inserted() {
+        let path = DestinationPath::parse("periods[1].home").unwrap();
+        let mut destination = Map::new();
+        assert_eq!(path.get(&destination), None);
+        path.insert(&mut destination, json!(3));
+        assert_eq!(path.get(&destination), Some(&json!(3)));
+        assert_eq!(
+            DestinationPath::parse("periods[0].home")
+                .unwrap()
+                .get(&destination),
+            None
+        );
+    }
+
+    #[test]
+    fn inserts_into_objects_and_arrays() {
+        let mut destination = Map::new();
+        assert!(DestinationPath::parse("periods[1].home")
+            .unwrap()
+            .insert(&mut destination, json!(3)));
+        assert!(!DestinationPath::parse("periods.home")
+            .unwrap()
+            .insert(&mut destination, json!(4)));
+        assert_eq!(
+            Value::Object(destination),
+            json!({ "periods": [null, { "home": 3 }] })
+        );
+    }
+}
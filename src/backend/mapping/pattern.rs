@@ -0,0 +1,222 @@
+//! Regular expressions for cleaning up console text, e.g. pulling the number
+//! out of `#12 HOLDING` or collapsing runs of spaces in messages. Patterns use
+//! the [`regex`] crate's syntax.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    parsed::{Parse, Parsed},
+    text::value_text,
+    transformation::TransformationError,
+};
+
+fn default_group() -> usize {
+    1
+}
+
+/// Extracts the text matched by a capture group of the first match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegexExtract {
+    pub pattern: Parsed<Regex>,
+    /// The capture group to extract, or 0 for the whole match
+    #[serde(default = "default_group")]
+    pub group: usize,
+    /// Text to preview the pattern with when there's no data from the console
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sample: String,
+}
+
+impl RegexExtract {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const EMPTY: RegexExtract = RegexExtract {
+        pattern: Parsed::EMPTY,
+        group: 1,
+        sample: String::new(),
+    };
+
+    /// Checks that the pattern compiles and has the capture group.
+    pub fn validate(&self) -> Result<(), String> {
+        let regex = self.pattern.parsed().map_err(|err| err.to_string())?;
+        // the whole match counts as a group
+        if self.group >= regex.captures_len() {
+            return Err(format!(
+                "there's no capture group {} in the pattern",
+                self.group
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn extract(&self, value: &Value) -> Result<Value, TransformationError> {
+        let regex = compiled(&self.pattern)?;
+        let text = value_text(value)?;
+        let captures = regex
+            .captures(&text)
+            .ok_or_else(|| TransformationError::NoPatternMatch(text.clone()))?;
+        if self.group >= captures.len() {
+            return Err(TransformationError::Pattern(format!(
+                "there's no capture group {}",
+                self.group
+            )));
+        }
+        // a group in a branch that didn't match extracts nothing
+        Ok(Value::String(
+            captures
+                .get(self.group)
+                .map_or("", |group| group.as_str())
+                .to_owned(),
+        ))
+    }
+}
+
+/// Replaces every match with a replacement, which can refer to capture groups
+/// like `$1` or `${name}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegexReplace {
+    pub pattern: Parsed<Regex>,
+    pub replacement: String,
+    /// Text to preview the pattern with when there's no data from the console
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sample: String,
+}
+
+impl RegexReplace {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const EMPTY: RegexReplace = RegexReplace {
+        pattern: Parsed::EMPTY,
+        replacement: String::new(),
+        sample: String::new(),
+    };
+
+    /// Checks that the pattern compiles.
+    pub fn validate(&self) -> Result<(), String> {
+        self.pattern
+            .parsed()
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn replace(&self, value: &Value) -> Result<Value, TransformationError> {
+        let regex = compiled(&self.pattern)?;
+        let text = value_text(value)?;
+        Ok(Value::String(
+            regex
+                .replace_all(&text, self.replacement.as_str())
+                .into_owned(),
+        ))
+    }
+}
+
+impl Parse for Regex {
+    type Error = regex::Error;
+
+    fn parse(source: &str) -> Result<Self, regex::Error> {
+        Regex::new(source)
+    }
+}
+
+/// The pattern's regex, which is compiled the first time it's used.
+fn compiled(pattern: &Parsed<Regex>) -> Result<&Regex, TransformationError> {
+    pattern
+        .parsed()
+        .map_err(|err| TransformationError::Pattern(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{RegexExtract, RegexReplace};
+    use crate::backend::mapping::transformation::TransformationError;
+
+    fn extract(pattern: &str, group: usize) -> RegexExtract {
+        RegexExtract {
+            pattern: pattern.to_owned().into(),
+            group,
+            ..RegexExtract::EMPTY
+        }
+    }
+
+    fn replace(pattern: &str, replacement: &str) -> RegexReplace {
+        RegexReplace {
+            pattern: pattern.to_owned().into(),
+            replacement: replacement.to_owned(),
+            ..RegexReplace::EMPTY
+        }
+    }
+
+    #[test]
+    fn checks_the_pattern_and_group() {
+        assert_eq!(extract(r"#(\d+)", 1).validate(), Ok(()));
+        assert_eq!(extract(r"#(\d+)", 0).validate(), Ok(()));
+        assert_eq!(
+            extract(r"#(\d+)", 2).validate(),
+            Err("there's no capture group 2 in the pattern".to_owned())
+        );
+        assert!(extract(r"#(\d+", 1).validate().is_err());
+        assert_eq!(replace(r"\s+", " ").validate(), Ok(()));
+        assert!(replace(r"[", " ").validate().is_err());
+    }
+
+    #[test]
+    fn extracts_a_group_of_the_first_match() {
+        let foul = json!("#12 HOLDING #4");
+        assert_eq!(extract(r"#(\d+)", 1).extract(&foul).unwrap(), json!("12"));
+        assert_eq!(extract(r"#(\d+)", 0).extract(&foul).unwrap(), json!("#12"));
+        assert_eq!(
+            extract(r"(\d+)", 1).extract(&json!(1234)).unwrap(),
+            json!("1234")
+        );
+    }
+
+    #[test]
+    fn reports_text_that_doesnt_match() {
+        assert!(matches!(
+            extract(r"#(\d+)", 1).extract(&json!("TIMEOUT")),
+            Err(TransformationError::NoPatternMatch(text)) if text == "TIMEOUT"
+        ));
+        assert!(matches!(
+            extract(r"#(\d+)", 1).extract(&Value::Null),
+            Err(TransformationError::UnexpectedSourceType(Value::Null))
+        ));
+        assert!(matches!(
+            extract(r"#(\d+)", 2).extract(&json!("#12")),
+            Err(TransformationError::Pattern(_))
+        ));
+    }
+
+    #[test]
+    fn extracts_nothing_from_groups_that_didnt_match() {
+        let either = extract(r"#(\d+)|([A-Z]+)", 1);
+        assert_eq!(either.extract(&json!("#12")).unwrap(), json!("12"));
+        assert_eq!(either.extract(&json!("HOLDING")).unwrap(), json!(""));
+    }
+
+    #[test]
+    fn replaces_every_match() {
+        assert_eq!(
+            replace(r"\s+", " ")
+                .replace(&json!("HOME   TEAM  WINS"))
+                .unwrap(),
+            json!("HOME TEAM WINS")
+        );
+        assert_eq!(
+            replace(r"(\d+)-(\d+)", "$2 to $1")
+                .replace(&json!("12-34"))
+                .unwrap(),
+            json!("34 to 12")
+        );
+        assert_eq!(
+            replace(r"(?<home>\d+)", "[${home}]")
+                .replace(&json!("1 and 2"))
+                .unwrap(),
+            json!("[1] and [2]")
+        );
+        assert!(matches!(
+            replace(r"[", " ").replace(&json!("text")),
+            Err(TransformationError::Pattern(_))
+        ));
+    }
+}
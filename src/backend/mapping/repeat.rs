@@ -0,0 +1,272 @@
+//! Repeats mapping items over a numbered family of source fields, e.g.
+//! `lane_1_place`, `lane_2_place`, ..., outputting an array with an object for
+//! each number. Lane results or a player foul board are one repeat instead of
+//! a mapping item per field.
+
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{
+    change::number, insert_at_path, path::DestinationPath, MapError, MappingItem, MappingState,
+};
+
+/// Stands for the number in the source fields of a repeat's items.
+pub const NUMBER_PLACEHOLDER: char = '#';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatMapping {
+    pub enabled: bool,
+    /// Where the array is output, which can be a path
+    pub destination_field: String,
+    /// The fields of each object. Source fields have `#` in place of the
+    /// number, e.g. `lane_#_place`, and destination fields are within the
+    /// object.
+    pub items: Vec<MappingItem>,
+    /// The destination field to sort the objects by, which can be a path
+    /// within the object, or blank to keep them in order of number
+    #[serde(default)]
+    pub sort_by: String,
+    #[serde(default)]
+    pub sort_descending: bool,
+}
+
+impl Default for RepeatMapping {
+    fn default() -> Self {
+        RepeatMapping {
+            enabled: true,
+            destination_field: Default::default(),
+            items: vec![Default::default()],
+            sort_by: Default::default(),
+            sort_descending: false,
+        }
+    }
+}
+
+impl RepeatMapping {
+    /// The numbers that fill in the items' source fields to make one of
+    /// `keys`, in order, with how they're written in the key (e.g. `01`).
+    pub fn numbers<'k>(&self, keys: impl IntoIterator<Item = &'k String>) -> Vec<(u64, &'k str)> {
+        let patterns: Vec<_> = self
+            .items
+            .iter()
+            .filter_map(|item| item.source_field.split_once(NUMBER_PLACEHOLDER))
+            .collect();
+        let mut numbers: Vec<_> = keys
+            .into_iter()
+            .filter_map(|key| {
+                patterns
+                    .iter()
+                    .find_map(|(prefix, suffix)| number_in(key, prefix, suffix))
+            })
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup_by_key(|(number, _)| *number);
+        numbers
+    }
+
+    /// The array to output, or `None` if the repeat is disabled.
+    pub fn map(
+        &self,
+        source: &Map<String, Value>,
+        exclude_incomplete_data: bool,
+        state: &mut MappingState,
+    ) -> Result<Option<Value>, MapError> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        // checked before mapping so a bad path is reported even with no objects
+        let sort_by = if self.sort_by.is_empty() {
+            None
+        } else {
+            Some(DestinationPath::parse(&self.sort_by).map_err(MapError::DestinationPath)?)
+        };
+        let mut objects = Vec::new();
+        for (number, digits) in self.numbers(source.keys()) {
+            let mut object = Map::with_capacity(self.items.len());
+            let mut written_paths = Vec::with_capacity(self.items.len());
+            for item in &self.items {
+                let source_field = item.source_field.replacen(NUMBER_PLACEHOLDER, digits, 1);
+                // not every number has every field
+                if !source_field.is_empty() && !source.contains_key(&source_field) {
+                    continue;
+                }
+                // names the field in errors and keeps its state apart from
+                // the other numbers'
+                let destination_field = format!(
+                    "{}[#{}].{}",
+                    self.destination_field, number, item.destination_field
+                );
+                let Some(value) = item.map_field(
+                    &source_field,
+                    &destination_field,
+                    source,
+                    exclude_incomplete_data,
+                    state,
+                )?
+                else {
+                    continue;
+                };
+                insert_at_path(
+                    &mut object,
+                    &mut written_paths,
+                    &item.destination_field,
+                    value,
+                )?;
+            }
+            objects.push(object);
+        }
+        if let Some(sort_by) = sort_by {
+            objects.sort_by(|a, b| {
+                compare_sort_values(sort_by.get(a), sort_by.get(b), self.sort_descending)
+            });
+        }
+        Ok(Some(Value::Array(
+            objects.into_iter().map(Value::Object).collect(),
+        )))
+    }
+}
+
+/// The number in `key` between `prefix` and `suffix`, if that's all digits.
+fn number_in<'k>(key: &'k str, prefix: &str, suffix: &str) -> Option<(u64, &'k str)> {
+    let digits = key.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    if digits.is_empty() || !digits.chars().all(|char| char.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, digits))
+}
+
+enum SortValue<'v> {
+    Number(f64),
+    Text(&'v str),
+}
+
+/// Missing and blank values have nothing to sort by.
+fn sort_value(value: Option<&Value>) -> Option<SortValue<'_>> {
+    let value = value?;
+    if let Some(number) = number(value) {
+        return Some(SortValue::Number(number));
+    }
+    match value {
+        Value::String(string) if !string.trim().is_empty() => Some(SortValue::Text(string.trim())),
+        _ => None,
+    }
+}
+
+/// Numbers (including numeric text) sort before text, and missing or blank
+/// values last either way, e.g. lanes without a place.
+fn compare_sort_values(a: Option<&Value>, b: Option<&Value>, descending: bool) -> Ordering {
+    let ordering = match (sort_value(a), sort_value(b)) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(SortValue::Number(a)), Some(SortValue::Number(b))) => a.total_cmp(&b),
+        (Some(SortValue::Number(_)), Some(SortValue::Text(_))) => Ordering::Less,
+        (Some(SortValue::Text(_)), Some(SortValue::Number(_))) => Ordering::Greater,
+        (Some(SortValue::Text(a)), Some(SortValue::Text(b))) => a.cmp(b),
+    };
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// The numbered families in `keys` as source fields for a repeat's items, e.g.
+/// `lane_#_place` for `lane_1_place`, `lane_2_place`, ... Any run of digits
+/// can be the number, and a family has at least two.
+pub fn numbered_families(keys: &[String]) -> Vec<String> {
+    let mut families: BTreeMap<String, usize> = BTreeMap::new();
+    for key in keys {
+        let bytes = key.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if !bytes[i].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let family = format!("{}{NUMBER_PLACEHOLDER}{}", &key[..start], &key[i..]);
+            *families.entry(family).or_default() += 1;
+        }
+    }
+    families
+        .into_iter()
+        .filter(|(_, count)| *count >= 2)
+        .map(|(family, _)| family)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn item(source_field: &str, destination_field: &str) -> MappingItem {
+        MappingItem {
+            source_field: source_field.to_owned(),
+            destination_field: destination_field.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn lanes(sort_by: &str, sort_descending: bool) -> RepeatMapping {
+        RepeatMapping {
+            destination_field: "lanes".to_owned(),
+            items: vec![
+                item("lane_#_name", "name"),
+                item("lane_#_place", "result.place"),
+            ],
+            sort_by: sort_by.to_owned(),
+            sort_descending,
+            ..Default::default()
+        }
+    }
+
+    fn names(repeat: &RepeatMapping) -> Result<Vec<Value>, MapError> {
+        let source = json!({
+            "lane_1_name": "A", "lane_1_place": " 2",
+            "lane_2_name": "B", "lane_2_place": "  ",
+            "lane_3_name": "C", "lane_3_place": "1",
+        });
+        let lanes = repeat
+            .map(source.as_object().unwrap(), false, &mut Default::default())?
+            .unwrap();
+        Ok(lanes
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|lane| lane["name"].clone())
+            .collect())
+    }
+
+    #[test]
+    fn keeps_number_order_without_sort_by() {
+        assert_eq!(names(&lanes("", false)).unwrap(), ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn sorts_by_a_nested_field_with_blanks_last() {
+        assert_eq!(
+            names(&lanes("result.place", false)).unwrap(),
+            ["C", "A", "B"]
+        );
+        assert_eq!(
+            names(&lanes("result.place", true)).unwrap(),
+            ["A", "C", "B"]
+        );
+    }
+
+    #[test]
+    fn reports_an_invalid_sort_by() {
+        assert!(matches!(
+            names(&lanes("result.", false)),
+            Err(MapError::DestinationPath(_))
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+//! Format strings that build text from several source fields, e.g.
+//! `{period|AppendOrdinalSuffix} {main_clock_time}`.
+//!
+//! Each `{...}` placeholder names a source field (or `value` for the mapping
+//! item's own source value), optionally followed by transformations to apply
+//! to it in order, separated by `|`. Transformations are named like they're
+//! saved in profiles, e.g. `TimeMinutes`. `{{` and `}}` are literal braces.
+
+use std::{error::Error, fmt::Display};
+
+use serde_json::{Map, Value};
+
+use super::{
+    parsed::Parse,
+    transformation::{Transformation, TransformationError},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateParseError {
+    /// Where the error is, in characters from the start of the template
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for TemplateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at character {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl Error for TemplateParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Placeholder {
+    field: String,
+    transformations: Vec<Transformation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A parsed template, ready to render against source maps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template(Vec<Part>);
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateParseError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().enumerate().peekable();
+        while let Some((position, char)) = chars.next() {
+            match char {
+                '{' if chars.next_if(|(_, next)| *next == '{').is_some() => text.push('{'),
+                '}' if chars.next_if(|(_, next)| *next == '}').is_some() => text.push('}'),
+                '}' => {
+                    return Err(TemplateParseError {
+                        position,
+                        message: "unmatched }, use }} for a literal brace".to_owned(),
+                    })
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, char)) => placeholder.push(char),
+                            None => {
+                                return Err(TemplateParseError {
+                                    position,
+                                    message: "the placeholder is never closed".to_owned(),
+                                })
+                            }
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Placeholder(parse_placeholder(
+                        &placeholder,
+                        position,
+                    )?));
+                }
+                char => text.push(char),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Renders the template with `value` as the mapping item's source value
+    /// and `source` as the whole source map.
+    pub fn render(
+        &self,
+        value: &Value,
+        source: &Map<String, Value>,
+    ) -> Result<String, TransformationError> {
+        let mut rendered = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Placeholder(placeholder) => {
+                    let mut field_value = match placeholder.field.as_str() {
+                        "value" => value.clone(),
+                        field => source.get(field).cloned().ok_or_else(|| {
+                            TransformationError::Template(format!(
+                                "source field {} does not exist",
+                                field
+                            ))
+                        })?,
+                    };
+                    for transformation in &placeholder.transformations {
+                        field_value = transformation.transform(&field_value, source).map_err(
+                            |err| match err {
+                                TransformationError::UnexpectedSourceType(Value::Null) => {
+                                    TransformationError::MissingField(placeholder.field.clone())
+                                }
+                                err => TransformationError::Template(format!(
+                                    "{} in {{{}}}",
+                                    err, placeholder.field
+                                )),
+                            },
+                        )?;
+                    }
+                    match field_value {
+                        Value::Null => {
+                            return Err(TransformationError::MissingField(
+                                placeholder.field.clone(),
+                            ))
+                        }
+                        Value::String(string) => rendered.push_str(&string),
+                        value => rendered.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+impl Parse for Template {
+    type Error = TemplateParseError;
+
+    fn parse(source: &str) -> Result<Self, TemplateParseError> {
+        Template::parse(source)
+    }
+}
+
+fn parse_placeholder(
+    placeholder: &str,
+    position: usize,
+) -> Result<Placeholder, TemplateParseError> {
+    let mut segments = placeholder.split('|').map(str::trim);
+    let field = segments.next().unwrap_or_default().to_owned();
+    if field.is_empty() {
+        return Err(TemplateParseError {
+            position,
+            message: "the placeholder doesn't name a field".to_owned(),
+        });
+    }
+    let transformations = segments
+        .map(|name| {
+            // only transformations without parameters can be named here
+            serde_json::from_value::<Transformation>(Value::String(name.to_owned())).map_err(|_| {
+                TemplateParseError {
+                    position,
+                    message: format!("there's no transformation called {}", name),
+                }
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Placeholder {
+        field,
+        transformations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use super::{Template, TemplateParseError};
+    use crate::backend::mapping::transformation::{Transformation, TransformationError};
+
+    fn source() -> Map<String, Value> {
+        json!({
+            "period": 4,
+            "main_clock_time": " 5:00",
+            "home_team_name": "Hawks",
+            "possession": null,
+            "shot_clock": "  ",
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    fn render(template: &str) -> Result<String, TransformationError> {
+        Template::parse(template)
+            .unwrap_or_else(|err| panic!("{template:?}: {err}"))
+            .render(&json!("Q"), &source())
+    }
+
+    fn rendered(template: &str) -> String {
+        render(template).unwrap_or_else(|err| panic!("{template:?}: {err}"))
+    }
+
+    fn parse_error(template: &str) -> TemplateParseError {
+        Template::parse(template).expect_err(template)
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        for (template, expected) in [
+            ("", ""),
+            ("no placeholders", "no placeholders"),
+            ("{value}{period} {main_clock_time}", "Q4  5:00"),
+            ("{ home_team_name } lead", "Hawks lead"),
+            ("{period}{period}", "44"),
+        ] {
+            assert_eq!(rendered(template), expected, "{template:?}");
+        }
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        for (template, expected) in [
+            ("{{period}}", "{period}"),
+            ("{{{period}}}", "{4}"),
+            ("}}", "}"),
+            ("{{", "{"),
+        ] {
+            assert_eq!(rendered(template), expected, "{template:?}");
+        }
+    }
+
+    #[test]
+    fn pipes_placeholders_through_transformations() {
+        for (template, expected) in [
+            ("{period|AppendOrdinalSuffix} period", "4th period"),
+            ("{main_clock_time|TimeMinutes}", "5"),
+            (
+                "{main_clock_time | TimeMinutes | AppendOrdinalSuffix}",
+                "5th",
+            ),
+            ("{home_team_name|AssertString}", "Hawks"),
+        ] {
+            assert_eq!(rendered(template), expected, "{template:?}");
+        }
+        assert!(matches!(
+            render("{home_team_name|AssertNumber}"),
+            Err(TransformationError::Template(err)) if err.contains("{home_team_name}")
+        ));
+    }
+
+    #[test]
+    fn reports_fields_without_data_as_missing() {
+        for (template, field) in [
+            ("{possession}", "possession"),
+            ("{period} {shot_clock|TimeSeconds}", "shot_clock"),
+        ] {
+            match render(template) {
+                Err(TransformationError::MissingField(missing)) => assert_eq!(missing, field),
+                other => panic!("{template:?}: {other:?}"),
+            }
+        }
+        assert!(matches!(
+            render("{shot_clock_time}"),
+            Err(TransformationError::Template(err)) if err.contains("shot_clock_time")
+        ));
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        for (template, position) in [
+            ("Q{period", 1),
+            ("period}", 6),
+            ("{}", 0),
+            ("a { | TimeMinutes}", 2),
+            ("{period|Nope}", 0),
+            ("{period|Lookup}", 0),
+        ] {
+            assert_eq!(parse_error(template).position, position, "{template:?}");
+        }
+        assert_eq!(
+            parse_error("{period|Nope}").to_string(),
+            "at character 1: there's no transformation called Nope"
+        );
+    }
+
+    #[test]
+    fn saves_as_the_source_text() {
+        let transformation = Transformation::Template(String::from("Q{period}").into());
+        let saved = serde_json::to_value(&transformation).unwrap();
+        assert_eq!(saved, json!({ "Template": "Q{period}" }));
+        assert_eq!(
+            serde_json::from_value::<Transformation>(saved).unwrap(),
+            transformation
+        );
+        assert_eq!(
+            transformation.transform(&Value::Null, &source()).unwrap(),
+            json!("Q4")
+        );
+    }
+}
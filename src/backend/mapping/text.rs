@@ -0,0 +1,299 @@
+//! Formatting for text fields in the composition, which have a fixed width,
+//! from console text that's padded with spaces and often all caps.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transformation::TransformationError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextCase {
+    #[default]
+    Upper,
+    Lower,
+    /// The first letter of each word in uppercase and the rest in lowercase
+    Title,
+}
+
+impl TextCase {
+    pub const ALL: [TextCase; 3] = [TextCase::Upper, TextCase::Lower, TextCase::Title];
+
+    pub fn apply(&self, value: &Value) -> Result<Value, TransformationError> {
+        let text = value_text(value)?;
+        Ok(Value::String(match self {
+            TextCase::Upper => text.to_uppercase(),
+            TextCase::Lower => text.to_lowercase(),
+            TextCase::Title => {
+                let mut titled = String::with_capacity(text.len());
+                let mut word_start = true;
+                for char in text.chars() {
+                    if word_start {
+                        titled.extend(char.to_uppercase());
+                    } else {
+                        titled.extend(char.to_lowercase());
+                    }
+                    word_start = !char.is_alphanumeric() && char != '\'';
+                }
+                titled
+            }
+        }))
+    }
+}
+
+impl Display for TextCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TextCase::Upper => "UPPERCASE",
+            TextCase::Lower => "lowercase",
+            TextCase::Title => "Title Case",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PadSide {
+    /// Adds the padding before the text, aligning it right
+    #[default]
+    Left,
+    /// Adds the padding after the text, aligning it left
+    Right,
+}
+
+impl PadSide {
+    pub const ALL: [PadSide; 2] = [PadSide::Left, PadSide::Right];
+}
+
+impl Display for PadSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PadSide::Left => "Pad on the left",
+            PadSide::Right => "Pad on the right",
+        })
+    }
+}
+
+/// Pads text shorter than a width with a character. Longer text is left as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Padding {
+    pub side: PadSide,
+    /// The width in characters
+    pub width: usize,
+    pub character: char,
+}
+
+impl Padding {
+    pub const DEFAULT: Padding = Padding {
+        side: PadSide::Left,
+        width: 0,
+        character: ' ',
+    };
+
+    pub fn pad(&self, value: &Value) -> Result<Value, TransformationError> {
+        let text = value_text(value)?;
+        let padding = self
+            .character
+            .to_string()
+            .repeat(self.width.saturating_sub(text.chars().count()));
+        Ok(Value::String(match self.side {
+            PadSide::Left => padding + &text,
+            PadSide::Right => text + &padding,
+        }))
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Cuts text longer than a width down to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Truncation {
+    /// The width in characters, including the ellipsis, or 0 for no limit so
+    /// text isn't cut to nothing before a width is entered
+    pub width: usize,
+    /// Whether to end cut text with `…` to show it was cut
+    pub ellipsis: bool,
+}
+
+impl Truncation {
+    pub const DEFAULT: Truncation = Truncation {
+        width: 0,
+        ellipsis: false,
+    };
+
+    pub fn truncate(&self, value: &Value) -> Result<Value, TransformationError> {
+        let text = value_text(value)?;
+        if self.width == 0 || text.chars().count() <= self.width {
+            return Ok(Value::String(text));
+        }
+        Ok(Value::String(if self.ellipsis {
+            text.chars()
+                .take(self.width - 1)
+                .chain(std::iter::once('…'))
+                .collect()
+        } else {
+            text.chars().take(self.width).collect()
+        }))
+    }
+}
+
+/// Pads the whole part of a number with zeros to at least `digits` digits,
+/// e.g. `7` to `07` or `-4.5` to `-04.5`. Blank text is missing, like a clock
+/// the console hasn't filled in.
+pub fn zero_pad(value: &Value, digits: usize) -> Result<Value, TransformationError> {
+    let text = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(string) if string.trim().is_empty() => {
+            return Err(TransformationError::UnexpectedSourceType(Value::Null))
+        }
+        Value::String(string) => string.trim().to_owned(),
+        _ => return Err(TransformationError::UnexpectedSourceType(value.clone())),
+    };
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", text.as_str()),
+    };
+    let (whole, fraction) = match unsigned.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let is_digits = |part: &str| part.chars().all(|char| char.is_ascii_digit());
+    if whole.is_empty()
+        || !is_digits(whole)
+        || fraction.is_some_and(|fraction| !is_digits(fraction))
+    {
+        return Err(TransformationError::UnexpectedSourceType(value.clone()));
+    }
+    Ok(Value::String(match fraction {
+        Some(fraction) => format!("{sign}{whole:0>digits$}.{fraction}"),
+        None => format!("{sign}{whole:0>digits$}"),
+    }))
+}
+
+/// The text of a value, with numbers and booleans written out like in JSON.
+pub(super) fn value_text(value: &Value) -> Result<String, TransformationError> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+        _ => Err(TransformationError::UnexpectedSourceType(value.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{zero_pad, PadSide, Padding, TextCase, Truncation};
+    use crate::backend::mapping::transformation::TransformationError;
+
+    fn text(result: Result<Value, TransformationError>) -> String {
+        match result.unwrap_or_else(|err| panic!("{err}")) {
+            Value::String(text) => text,
+            value => panic!("{value} isn't text"),
+        }
+    }
+
+    #[test]
+    fn changes_case() {
+        for (case, expected) in [
+            (TextCase::Upper, "O'BRIEN-SMITH 3RD"),
+            (TextCase::Lower, "o'brien-smith 3rd"),
+            (TextCase::Title, "O'brien-Smith 3rd"),
+        ] {
+            assert_eq!(text(case.apply(&json!("o'BRIEN-smith 3rd"))), expected);
+        }
+        assert_eq!(text(TextCase::Upper.apply(&json!(12))), "12");
+        assert!(TextCase::Upper.apply(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn pads_short_text_on_either_side() {
+        let padding = |side, width, character| Padding {
+            side,
+            width,
+            character,
+        };
+        for (padding, expected) in [
+            (padding(PadSide::Left, 5, ' '), "   ab"),
+            (padding(PadSide::Right, 5, '.'), "ab..."),
+            (padding(PadSide::Left, 2, '*'), "ab"),
+            (padding(PadSide::Left, 1, '*'), "ab"),
+            (Padding::DEFAULT, "ab"),
+        ] {
+            assert_eq!(text(padding.pad(&json!("ab"))), expected, "{padding:?}");
+        }
+        assert_eq!(text(padding(PadSide::Left, 3, '0').pad(&json!(7))), "007");
+    }
+
+    #[test]
+    fn truncates_long_text() {
+        let truncation = |width, ellipsis| Truncation { width, ellipsis };
+        for (truncation, expected) in [
+            (truncation(5, false), "Hawks"),
+            (truncation(4, false), "Hawk"),
+            (truncation(6, true), "Hawks"),
+            (truncation(5, true), "Hawks"),
+            (truncation(4, true), "Haw…"),
+            (truncation(1, true), "…"),
+            (Truncation::DEFAULT, "Hawks"),
+        ] {
+            assert_eq!(
+                text(truncation.truncate(&json!("Hawks"))),
+                expected,
+                "{truncation:?}"
+            );
+        }
+        assert_eq!(text(truncation(2, true).truncate(&json!("Ünï"))), "Ü…");
+    }
+
+    #[test]
+    fn zero_pads_the_whole_part_of_numbers() {
+        for (value, expected) in [
+            (json!(7), "07"),
+            (json!("7"), "07"),
+            (json!(" 7 "), "07"),
+            (json!("123"), "123"),
+            (json!(-4.5), "-04.5"),
+            (json!("-4.5"), "-04.5"),
+            (json!("4.05"), "04.05"),
+        ] {
+            assert_eq!(text(zero_pad(&value, 2)), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn doesnt_zero_pad_anything_else() {
+        for value in [
+            json!("7a"),
+            json!("4.5.1"),
+            json!(".5"),
+            json!("-"),
+            json!(true),
+        ] {
+            assert!(
+                matches!(
+                    zero_pad(&value, 2),
+                    Err(TransformationError::UnexpectedSourceType(ref unexpected)) if *unexpected == value
+                ),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn blank_text_is_missing() {
+        for value in [json!(""), json!("  "), Value::Null] {
+            assert!(
+                matches!(
+                    zero_pad(&value, 2),
+                    Err(TransformationError::UnexpectedSourceType(Value::Null))
+                ),
+                "{value}"
+            );
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use serde::{de::Error, Deserialize, Serialize};
 use serde_json::Value;
 
-use super::{mapping::Mapping, sports::DynamicSportType};
+use super::{
+    mapping::Mapping,
+    sports::{custom::CustomSport, raw::RawFieldDefinition, DynamicSportType},
+    stream::serial::SerialSettings,
+};
 
 pub type Profile = ProfileV2;
 pub type ProfileCompositionMapping = ProfileV2CompositionMapping;
@@ -16,6 +20,30 @@ pub struct ProfileV2 {
     pub exclude_incomplete_data: bool,
     pub sport_type: Option<DynamicSportType>,
     pub mappings: Vec<ProfileV2CompositionMapping>,
+    /// Profiles saved before serial settings existed get the 19200 8N1 defaults.
+    #[serde(default)]
+    pub serial: SerialSettings,
+    /// How long the console can go without sending new data before the payload
+    /// is marked stale, in milliseconds. 0 never marks it stale.
+    #[serde(default = "default_stale_data_timeout_ms")]
+    pub stale_data_timeout_ms: u64,
+    /// Named fields cut out of item text, used by the raw sport
+    #[serde(default)]
+    pub raw_fields: Vec<RawFieldDefinition>,
+    /// The layout of the custom sport, when that's the sport type. It's kept
+    /// in the profile so the profile works without the sport's file.
+    #[serde(default)]
+    pub custom_sport: Option<CustomSport>,
+    /// Whether destination fields are paths (see
+    /// [`crate::backend::mapping::path`]). Profiles saved before paths existed
+    /// don't have this, and their destination fields are escaped when they're
+    /// imported so they stay plain fields.
+    #[serde(default)]
+    pub destination_paths: bool,
+}
+
+fn default_stale_data_timeout_ms() -> u64 {
+    3000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -35,6 +63,11 @@ impl Default for ProfileV2 {
             exclude_incomplete_data: true,
             name: "New profile".to_owned(),
             mappings: vec![],
+            serial: Default::default(),
+            stale_data_timeout_ms: default_stale_data_timeout_ms(),
+            raw_fields: vec![],
+            custom_sport: None,
+            destination_paths: true,
         }
     }
 }
@@ -81,11 +114,26 @@ impl Into<ProfileV2> for ProfileV1 {
                 mapping: self.mapping,
                 subcomp_name: self.subcomp_name,
             }],
+            serial: Default::default(),
+            stale_data_timeout_ms: default_stale_data_timeout_ms(),
+            raw_fields: vec![],
+            custom_sport: None,
+            destination_paths: false,
         }
     }
 }
 
 impl Profile {
+    /// The named fields of the raw or custom sport, read from the sport's
+    /// data.
+    pub fn sport_fields(&self) -> &[RawFieldDefinition] {
+        match (self.sport_type, &self.custom_sport) {
+            (Some(DynamicSportType::Custom), Some(custom_sport)) => &custom_sport.fields,
+            (Some(DynamicSportType::Custom), None) => &[],
+            _ => &self.raw_fields,
+        }
+    }
+
     pub fn export(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(&self)
     }
@@ -106,13 +154,84 @@ impl Profile {
                 serde::de::Unexpected::Other("not a string"),
                 &"string",
             ))?;
-        Ok(match version.split(".").next().unwrap() {
+        let mut profile: Self = match version.split(".").next().unwrap() {
             "1" => serde_json::from_value::<ProfileV1>(value)?.into(),
             "2" => serde_json::from_value::<ProfileV2>(value)?.into(),
             _ => Err(serde_json::Error::invalid_value(
                 serde::de::Unexpected::Other("unknown version"),
                 &"1.0 or 2.0",
             ))?,
-        })
+        };
+        if !profile.destination_paths {
+            for mapping in &mut profile.mappings {
+                mapping.mapping.escape_destination_fields();
+            }
+            profile.destination_paths = true;
+        }
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::backend::mapping::MappingState;
+
+    /// Saved before destination fields were paths, so `score.home` is one
+    /// field
+    const OLD_PROFILE: &str = r#"{
+        "name": "Basketball",
+        "ui_version": "2.2.0",
+        "data_stream_url": "http://localhost:8000/",
+        "multiple_requests": false,
+        "exclude_incomplete_data": false,
+        "sport_type": null,
+        "mappings": [{
+            "subcomp_name": "Scoreboard",
+            "enabled_checkbox_name": null,
+            "mapping": {
+                "items": [{
+                    "enabled": true,
+                    "source_field": "home_score",
+                    "transformation": "None",
+                    "destination_field": "score.home"
+                }]
+            }
+        }]
+    }"#;
+
+    fn map(profile: &Profile) -> serde_json::Value {
+        profile.mappings[0]
+            .mapping
+            .map(
+                &json!({ "home_score": "5" }),
+                false,
+                &mut MappingState::default(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn old_profiles_keep_dotted_names_as_fields() {
+        let profile = Profile::import(OLD_PROFILE).unwrap();
+        assert!(profile.destination_paths);
+        assert_eq!(map(&profile), json!({ "score.home": "5" }));
+    }
+
+    #[test]
+    fn reimporting_doesnt_escape_again() {
+        let profile = Profile::import(OLD_PROFILE).unwrap();
+        let profile = Profile::import(&profile.export().unwrap()).unwrap();
+        assert_eq!(map(&profile), json!({ "score.home": "5" }));
+    }
+
+    #[test]
+    fn new_profiles_read_dotted_names_as_paths() {
+        let mut profile = Profile::import(OLD_PROFILE).unwrap();
+        profile.mappings[0].mapping.items[0].destination_field = "score.home".to_owned();
+        let profile = Profile::import(&profile.export().unwrap()).unwrap();
+        assert_eq!(map(&profile), json!({ "score": { "home": "5" } }));
     }
 }
@@ -1,11 +1,37 @@
+use std::collections::HashMap;
+
 use serde_json::{Map, Value};
 
-use super::{mapping::MapError, profile::ProfileCompositionMapping};
+use super::{
+    mapping::{MapError, MappingState},
+    profile::ProfileCompositionMapping,
+};
 
 // Same as in assets/root_composition_script.js
 const APPLY_CHECKBOX_KEY: &str = "__APPLY_CHECKBOX";
+const TIMESTAMP_KEY: &str = "__TIMESTAMP";
+const STATUS_KEY: &str = "__STATUS";
+
+/// Whether the console is still sending data, reported to the composition so
+/// it can hide or dim the graphics when the data is frozen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataStatus {
+    Live,
+    Stale,
+}
 
-/// Serialize the mappings to a JSON object.
+impl DataStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DataStatus::Live => "live",
+            DataStatus::Stale => "stale",
+        }
+    }
+}
+
+/// Serialize the mappings to a JSON object. `states` holds what each subcomp's
+/// mapping remembers between payloads, and should be kept between calls. It
+/// only takes in the payload if every mapping succeeds.
 ///
 /// # Panics
 ///
@@ -15,19 +41,41 @@ pub fn serialize_mappings(
     source: &Value,
     exclude_incomplete_data: bool,
     timestamp: Option<i64>,
+    status: Option<DataStatus>,
+    states: &mut HashMap<String, MappingState>,
 ) -> Result<Value, MapError> {
-    let mut map = Map::with_capacity(mappings.len() + matches!(timestamp, Some(_)) as usize);
+    let mut map = Map::with_capacity(
+        mappings.len() + timestamp.is_some() as usize + status.is_some() as usize,
+    );
     if let Some(timestamp) = timestamp {
         map.insert(
-            String::from("__TIMESTAMP"),
+            String::from(TIMESTAMP_KEY),
             Value::Number(
                 serde_json::Number::from_f64(timestamp as f64)
                     .expect("failed to convert timestamp to JSON number"),
             ),
         );
     }
+    if let Some(status) = status {
+        map.insert(
+            String::from(STATUS_KEY),
+            Value::String(status.as_str().to_owned()),
+        );
+    }
     for comp_mapping in mappings {
-        let mut mapped_obj = comp_mapping.mapping.map(&source, exclude_incomplete_data)?;
+        let mapped_obj = comp_mapping.mapping.map(
+            &source,
+            exclude_incomplete_data,
+            states.entry(comp_mapping.subcomp_name.clone()).or_default(),
+        );
+        // the payload isn't sent, so nothing it changed is kept
+        let mut mapped_obj = match mapped_obj {
+            Ok(mapped_obj) => mapped_obj,
+            Err(err) => {
+                states.values_mut().for_each(MappingState::discard);
+                return Err(err);
+            }
+        };
         if let Some(ref key) = comp_mapping.enabled_checkbox_name {
             mapped_obj
                 .as_object_mut()
@@ -36,5 +84,6 @@ pub fn serialize_mappings(
         }
         map.insert(comp_mapping.subcomp_name.clone(), mapped_obj);
     }
+    states.values_mut().for_each(MappingState::commit);
     Ok(Value::Object(map))
 }
@@ -0,0 +1,164 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use daktronics_allsport_5000::{sports::Sport, RTDState};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+
+use super::{
+    sports::{raw::RawFieldDefinition, DynamicSportType},
+    stream::{
+        frame::encode_frame,
+        source::{StreamDataSource, StreamInput},
+    },
+};
+
+/// How far into the sport's data to look for fields when probing the layout.
+const MAX_PROBE_OFFSET: usize = 2048;
+/// Stop probing once this many offsets in a row didn't belong to any field.
+const MAX_PROBE_GAP: usize = 256;
+
+/// An in-process channel of raw frames standing in for a console.
+#[derive(Debug, Clone)]
+pub struct SimulatorFeed {
+    tx: UnboundedSender<Vec<u8>>,
+    rx: Arc<Mutex<UnboundedReceiver<Vec<u8>>>>,
+}
+
+impl SimulatorFeed {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            tx,
+            rx: Arc::new(Mutex::new(rx)),
+        }
+    }
+
+    pub fn send(&self, frame: Vec<u8>) {
+        // the receiver lives as long as any clone of the feed, including this one
+        let _ = self.tx.send(frame);
+    }
+
+    /// Waits for the next frame sent to the feed.
+    ///
+    /// This is cancel-safe since `UnboundedReceiver::recv` is.
+    pub async fn recv(&self) -> Vec<u8> {
+        match self.rx.lock().await.recv().await {
+            Some(frame) => frame,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Where a field lives in the sport's data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl FieldLayout {
+    /// Builds the frame that sets the field to `value`, padded or cut to the
+    /// field length. Numbers are right-aligned like they are on the console.
+    pub fn encode(&self, value: &str) -> Vec<u8> {
+        let value = value.chars().take(self.length).collect::<String>();
+        let text = if value.trim().parse::<f64>().is_ok() {
+            format!("{:>width$}", value.trim(), width = self.length)
+        } else {
+            format!("{:<width$}", value, width = self.length)
+        };
+        encode_frame(self.offset, &text)
+    }
+}
+
+/// The offsets and lengths of each serialized field of a sport.
+#[derive(Debug, Clone, Default)]
+pub struct SportLayout {
+    pub fields: BTreeMap<String, FieldLayout>,
+}
+
+impl SportLayout {
+    /// Works out where each field lives by writing to one offset at a time and
+    /// watching which serialized field changes. The raw and custom sports are
+    /// laid out by their named `fields` instead (see
+    /// [`crate::backend::profile::Profile::sport_fields`]).
+    pub async fn probe(
+        sport_type: DynamicSportType,
+        fields: Vec<RawFieldDefinition>,
+    ) -> Result<Self, String> {
+        if matches!(sport_type, DynamicSportType::Raw | DynamicSportType::Custom) {
+            return Self::of_named_fields(sport_type, &fields);
+        }
+        let feed = SimulatorFeed::new();
+        let data_source =
+            StreamDataSource::open(StreamInput::Simulator(feed.clone()), Default::default())
+                .expect("simulator data sources can always be opened");
+        let mut sport = sport_type.as_dynamic_sport(RTDState::new(data_source));
+        let mut previous = sport.serialize_to_value().map_err(|err| err.to_string())?;
+
+        let mut fields = BTreeMap::<String, FieldLayout>::new();
+        let mut last_hit: Option<usize> = None;
+        for offset in 1..=MAX_PROBE_OFFSET {
+            if last_hit.is_some_and(|last_hit| offset - last_hit > MAX_PROBE_GAP) {
+                break;
+            }
+            feed.send(encode_frame(offset, "1"));
+            sport
+                .rtd_state()
+                .update_async()
+                .await
+                .map_err(|err| err.to_string())?;
+            let current = sport.serialize_to_value().map_err(|err| err.to_string())?;
+            if let (Some(previous), Some(current)) = (previous.as_object(), current.as_object()) {
+                for (key, value) in current {
+                    if previous.get(key) != Some(value) {
+                        last_hit = Some(offset);
+                        fields
+                            .entry(key.clone())
+                            .and_modify(|layout| layout.length = offset - layout.offset + 1)
+                            .or_insert(FieldLayout { offset, length: 1 });
+                    }
+                }
+            }
+            previous = current;
+        }
+        log::info!(
+            target: "simulator",
+            "Found {} fields in the {} layout",
+            fields.len(),
+            sport_type
+        );
+        Ok(Self { fields })
+    }
+
+    /// The layout of the raw or custom sport, straight from its named fields'
+    /// definitions. Fields without a known length are left out, since there's
+    /// no telling how much text to send for them.
+    fn of_named_fields(
+        sport_type: DynamicSportType,
+        fields: &[RawFieldDefinition],
+    ) -> Result<Self, String> {
+        let mut layout = BTreeMap::new();
+        for field in fields {
+            match field.span() {
+                Some((offset, length)) => {
+                    layout.insert(field.name.clone(), FieldLayout { offset, length });
+                }
+                None => log::info!(
+                    target: "simulator",
+                    "Leaving {} out of the simulator since it doesn't have a length",
+                    field.name
+                ),
+            }
+        }
+        if layout.is_empty() {
+            return Err(format!(
+                "the {} sport has no named fields with a length to simulate; add some to \
+                 the profile, giving each a length or an item range",
+                sport_type
+            ));
+        }
+        Ok(Self { fields: layout })
+    }
+}
@@ -1,3 +1,7 @@
+pub mod custom;
+pub mod detection;
+pub mod raw;
+
 use std::fmt::{Debug, Display};
 
 use daktronics_allsport_5000::{
@@ -29,6 +33,7 @@ use daktronics_allsport_5000::{
     },
     RTDState,
 };
+use raw::{RawFieldDefinition, RawItems, RawSport};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -55,6 +60,11 @@ pub enum DynamicSportType {
     Volleyball,
     WaterPolo,
     Wrestling,
+    /// Each item's text as is, for consoles running a sport not listed here
+    Raw,
+    /// A sport loaded from a file, see [`custom::CustomSport`]. Its layout is
+    /// kept in the profile.
+    Custom,
 }
 
 impl Display for DynamicSportType {
@@ -82,6 +92,8 @@ impl Display for DynamicSportType {
             DynamicSportType::Volleyball => "Volleyball",
             DynamicSportType::WaterPolo => "Water Polo",
             DynamicSportType::Wrestling => "Wrestling",
+            DynamicSportType::Raw => "Raw RTD",
+            DynamicSportType::Custom => "Custom",
         })
     }
 }
@@ -106,7 +118,8 @@ impl RTDStateDataSource for DummyDataSource {
 }
 
 impl DynamicSportType {
-    pub const ALL: [DynamicSportType; 22] = [
+    /// The built-in sports. Custom sports are picked from their files.
+    pub const ALL: [DynamicSportType; 23] = [
         DynamicSportType::AutoRacing,
         DynamicSportType::Baseball,
         DynamicSportType::Basketball,
@@ -129,8 +142,16 @@ impl DynamicSportType {
         DynamicSportType::Volleyball,
         DynamicSportType::WaterPolo,
         DynamicSportType::Wrestling,
+        DynamicSportType::Raw,
     ];
 
+    /// Whether the sport has a layout of its own that packets can be checked
+    /// against. The raw sport takes whatever the console sends, and custom
+    /// sports are laid out by the profile.
+    pub fn is_detectable(&self) -> bool {
+        !matches!(self, DynamicSportType::Raw | DynamicSportType::Custom)
+    }
+
     pub fn as_dynamic_sport<DS: RTDStateDataSource>(
         &self,
         rtd_state: RTDState<DS>,
@@ -176,12 +197,25 @@ impl DynamicSportType {
             }
             DynamicSportType::WaterPolo => DynamicSport::WaterPolo(WaterPoloSport::new(rtd_state)),
             DynamicSportType::Wrestling => DynamicSport::Wrestling(WrestlingSport::new(rtd_state)),
+            DynamicSportType::Raw => DynamicSport::Raw(RawSport::new(rtd_state)),
+            DynamicSportType::Custom => {
+                DynamicSport::Raw(RawSport::new(rtd_state).without_items())
+            }
         }
     }
 
-    pub fn all_serialized_keys(&self) -> serde_json::Result<Vec<String>> {
+    /// The source fields the sport provides. `fields` are the named fields of
+    /// the raw and custom sports (see [`crate::backend::profile::ProfileV2::sport_fields`]).
+    /// The raw sport's item fields depend on what the console sends, so they
+    /// aren't listed.
+    pub fn all_serialized_keys(
+        &self,
+        fields: &[RawFieldDefinition],
+    ) -> serde_json::Result<Vec<String>> {
         let state = RTDState::new(DummyDataSource {});
-        let dynamic_sport = self.as_dynamic_sport(state);
+        let dynamic_sport = self
+            .as_dynamic_sport(state)
+            .with_raw_items(RawItems::default(), fields.to_vec());
         let value = dynamic_sport.serialize_to_value()?;
         match value {
             serde_json::Value::Object(value) => Ok(value.into_iter().map(|x| x.0).collect()),
@@ -213,6 +247,7 @@ pub enum DynamicSport<DS: RTDStateDataSource> {
     Volleyball(VolleyballSport<DS>),
     WaterPolo(WaterPoloSport<DS>),
     Wrestling(WrestlingSport<DS>),
+    Raw(RawSport<DS>),
 }
 
 impl<DS: RTDStateDataSource> Debug for DynamicSport<DS> {
@@ -250,6 +285,7 @@ impl<DS: RTDStateDataSource> daktronics_allsport_5000::sports::Sport<DS> for Dyn
             DynamicSport::Volleyball(x) => x.rtd_state(),
             DynamicSport::WaterPolo(x) => x.rtd_state(),
             DynamicSport::Wrestling(x) => x.rtd_state(),
+            DynamicSport::Raw(x) => x.rtd_state(),
         }
     }
 }
@@ -279,6 +315,16 @@ impl<DS: RTDStateDataSource> DynamicSport<DS> {
             DynamicSport::Volleyball(x) => serde_json::to_value(x),
             DynamicSport::WaterPolo(x) => serde_json::to_value(x),
             DynamicSport::Wrestling(x) => serde_json::to_value(x),
+            DynamicSport::Raw(x) => serde_json::to_value(x),
+        }
+    }
+
+    /// Gives the raw and custom sports the items read by the data source and
+    /// their named fields. Other sports are returned as they are.
+    pub fn with_raw_items(self, items: RawItems, fields: Vec<RawFieldDefinition>) -> Self {
+        match self {
+            DynamicSport::Raw(x) => DynamicSport::Raw(x.with_items(items, fields)),
+            other => other,
         }
     }
 }
@@ -0,0 +1,77 @@
+//! Sports defined in data files rather than by the upstream crate, e.g. for
+//! firmware variants or boards the crate doesn't know about yet.
+//!
+//! A sport file is JSON with the sport's name and the fields to read from the
+//! sport's data:
+//!
+//! ```json
+//! {
+//!   "name": "Swimming Relay",
+//!   "fields": [
+//!     { "name": "lane_1_time", "item_range": "0042101-0042108" },
+//!     { "name": "lane_1_place", "item_range": "0042109", "length": 2, "type": "integer" }
+//!   ]
+//! }
+//! ```
+//!
+//! The fields are read like the raw sport's named fields (see
+//! [`RawFieldDefinition`]).
+
+use std::{collections::HashSet, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{raw::RawFieldDefinition, DynamicSportType};
+
+/// The extension of sport files.
+pub const CUSTOM_SPORT_FILE_EXTENSION: &str = "json";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomSport {
+    pub name: String,
+    pub fields: Vec<RawFieldDefinition>,
+}
+
+impl CustomSport {
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        let source = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|err| err.to_string())?;
+        let sport = serde_json::from_str::<Self>(&source).map_err(|err| err.to_string())?;
+        if sport.fields.is_empty() {
+            return Err(format!("{} doesn't define any fields", sport.name));
+        }
+        for field in &sport.fields {
+            field.validate()?;
+        }
+        let mut names = HashSet::new();
+        if let Some(field) = sport.fields.iter().find(|field| !names.insert(&field.name)) {
+            return Err(format!("the field {} is defined more than once", field.name));
+        }
+        log::info!(
+            target: "sports",
+            "Loaded the custom sport {} with {} fields from {}",
+            sport.name,
+            sport.fields.len(),
+            path.display()
+        );
+        Ok(sport)
+    }
+}
+
+/// A sport to pick in the configure screen, either one of the built-in sports
+/// or a custom one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SportChoice {
+    BuiltIn(DynamicSportType),
+    Custom(CustomSport),
+}
+
+impl Display for SportChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SportChoice::BuiltIn(sport_type) => sport_type.fmt(f),
+            SportChoice::Custom(sport) => write!(f, "{} (custom)", sport.name),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Working out which sport the console is running from the packets it sends.
+//!
+//! Each sport lays its fields out at different offsets and uses a different
+//! amount of data, so the item numbers and text lengths of incoming packets
+//! are matched against every sport's layout (see [`SportLayout::probe`]).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OnceCell;
+
+use crate::backend::{
+    simulator::SportLayout,
+    stream::{
+        frame::frame_span,
+        serial::{sniff_console, SerialSettings},
+    },
+};
+
+use super::DynamicSportType;
+
+/// How many packets to see before guessing, so a handful of packets that
+/// happen to fit several sports doesn't produce a guess.
+const MIN_DETECTION_PACKETS: usize = 20;
+/// The share of packets that may run past a sport's data before the sport is
+/// ruled out, to allow for line noise.
+const MAX_OVERFLOW_RATIO: f32 = 0.05;
+
+static SPORT_LAYOUTS: OnceCell<Vec<(DynamicSportType, SportLayout)>> = OnceCell::const_new();
+
+/// The layout of every sport, probed the first time it's needed.
+async fn sport_layouts() -> &'static [(DynamicSportType, SportLayout)] {
+    SPORT_LAYOUTS
+        .get_or_init(|| async {
+            let mut layouts = Vec::new();
+            for sport_type in DynamicSportType::ALL
+                .into_iter()
+                .filter(DynamicSportType::is_detectable)
+            {
+                match SportLayout::probe(sport_type, Vec::new()).await {
+                    Ok(layout) => layouts.push((sport_type, layout)),
+                    Err(err) => log::warn!(
+                        target: "detect",
+                        "Couldn't probe the {} layout, leaving it out of detection: {}",
+                        sport_type,
+                        err
+                    ),
+                }
+            }
+            layouts
+        })
+        .await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SportCandidate {
+    pub sport_type: DynamicSportType,
+    /// The share of packets that start at one of the sport's fields, from 0
+    /// to 1
+    pub score: f32,
+}
+
+/// The sports that fit the packets seen so far, best first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SportDetection {
+    pub candidates: Vec<SportCandidate>,
+    pub packets: usize,
+}
+
+impl SportDetection {
+    fn from_spans(
+        spans: &BTreeMap<(usize, usize), usize>,
+        layouts: &[(DynamicSportType, SportLayout)],
+    ) -> Option<Self> {
+        let packets = spans.values().sum::<usize>();
+        if packets < MIN_DETECTION_PACKETS {
+            return None;
+        }
+        let mut candidates = layouts
+            .iter()
+            .filter_map(|(sport_type, layout)| {
+                let extent = layout
+                    .fields
+                    .values()
+                    .map(|field| field.offset + field.length)
+                    .max()
+                    .unwrap_or(0);
+                let field_offsets = layout
+                    .fields
+                    .values()
+                    .map(|field| field.offset)
+                    .collect::<BTreeSet<_>>();
+                let mut overflowing = 0;
+                let mut aligned = 0;
+                for (&(offset, length), &count) in spans {
+                    if offset + length > extent {
+                        overflowing += count;
+                    } else if field_offsets.contains(&offset) {
+                        aligned += count;
+                    }
+                }
+                (overflowing as f32 <= packets as f32 * MAX_OVERFLOW_RATIO).then_some((
+                    SportCandidate {
+                        sport_type: *sport_type,
+                        score: aligned as f32 / packets as f32,
+                    },
+                    extent,
+                ))
+            })
+            .collect::<Vec<_>>();
+        // prefer the tightest fit when sports score the same, since a packet
+        // that fits a small sport also fits every larger one
+        candidates.sort_by(|(a, a_extent), (b, b_extent)| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a_extent.cmp(b_extent))
+        });
+        Some(Self {
+            candidates: candidates
+                .into_iter()
+                .map(|(candidate, _)| candidate)
+                .collect(),
+            packets,
+        })
+    }
+
+    /// The sport the console is most likely running.
+    pub fn best(&self) -> Option<DynamicSportType> {
+        self.candidates.first().map(|candidate| candidate.sport_type)
+    }
+
+    /// Whether the packets fit `sport_type` as well as any other sport.
+    pub fn matches(&self, sport_type: DynamicSportType) -> bool {
+        match self.candidates.first() {
+            Some(best) => self
+                .candidates
+                .iter()
+                .any(|candidate| candidate.sport_type == sport_type && candidate.score >= best.score),
+            None => false,
+        }
+    }
+}
+
+/// A shared tally of the packets read by a stream, kept so the sport can be
+/// checked while the stream runs.
+#[derive(Debug, Clone, Default)]
+pub struct SportDetector(Arc<Mutex<BTreeMap<(usize, usize), usize>>>);
+
+impl SportDetector {
+    pub fn observe(&self, frame: &[u8]) {
+        if let Some(span) = frame_span(frame) {
+            *self
+                .0
+                .lock()
+                .expect("sport detector lock poisoned")
+                .entry(span)
+                .or_default() += 1;
+        }
+    }
+
+    /// Guesses the sport from the packets observed so far, once there are
+    /// enough of them.
+    pub async fn detect(&self) -> Option<SportDetection> {
+        let spans = self.0.lock().expect("sport detector lock poisoned").clone();
+        SportDetection::from_spans(&spans, sport_layouts().await)
+    }
+}
+
+/// Guesses the sport from a batch of frames, e.g. ones sniffed from a port.
+pub async fn detect_sport(frames: &[Vec<u8>]) -> Option<SportDetection> {
+    let detector = SportDetector::default();
+    for frame in frames {
+        detector.observe(frame);
+    }
+    detector.detect().await
+}
+
+/// Listens for the console on every serial port and guesses its sport,
+/// returning the port it was found on as well.
+pub async fn detect_sport_on_serial(
+    settings: SerialSettings,
+) -> Result<(String, SportDetection), String> {
+    let (port_name, frames) = sniff_console(settings).await.ok_or(
+        "couldn't find the console on any serial port; check the cable and serial settings",
+    )?;
+    let detection = detect_sport(&frames).await.ok_or_else(|| {
+        format!(
+            "only {} packets arrived on {}, which isn't enough to tell the sport",
+            frames.len(),
+            port_name
+        )
+    })?;
+    Ok((port_name, detection))
+}
@@ -0,0 +1,305 @@
+//! A sport for consoles the upstream crate doesn't model, exposing the text of
+//! each item the console sends as is.
+//!
+//! Every item received becomes a field named after its item number, e.g.
+//! `item_0042101` for the item written at offset 1. Profiles can also declare
+//! named fields that cut a slice out of the sport's data, which is also how
+//! custom sports (see [`super::custom`]) are laid out.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use daktronics_allsport_5000::{
+    rtd_state::data_source::RTDStateDataSource, sports::Sport, RTDState,
+};
+use serde::{ser::SerializeMap, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::backend::stream::frame::{frame_text, item_number, ITEM_NUMBER_PREFIX, MAX_OFFSET};
+
+pub const RAW_ITEM_KEY_PREFIX: &str = "item_";
+
+/// How a named field's text is turned into a value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawFieldType {
+    /// The text, trimmed
+    #[default]
+    Text,
+    /// A whole number, or null if the text isn't one
+    Integer,
+    /// A number, or null if the text isn't one
+    Number,
+    /// Whether anything but spaces was sent, like the console's indicators
+    Boolean,
+}
+
+impl RawFieldType {
+    fn parse(&self, text: &str) -> Value {
+        let text = text.trim();
+        match self {
+            RawFieldType::Text => Value::String(text.to_owned()),
+            RawFieldType::Integer => text.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+            RawFieldType::Number => text.parse::<f64>().map(Value::from).unwrap_or(Value::Null),
+            RawFieldType::Boolean => Value::Bool(!text.is_empty()),
+        }
+    }
+}
+
+/// A named field made from part of the sport's data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawFieldDefinition {
+    pub name: String,
+    /// The item number the field is in, e.g. `0042101`, or a range of item
+    /// numbers it spans, e.g. `0042101-0042108`. The padded form from the
+    /// console documentation (`0042100001`) works too.
+    #[serde(alias = "item_range")]
+    pub item_number: String,
+    /// Where the field starts in the item, from 1 like the console's offsets
+    #[serde(default)]
+    pub offset: usize,
+    /// The length of the field, or 0 for the rest of the item range (or of
+    /// the item's text for a single item)
+    #[serde(default)]
+    pub length: usize,
+    #[serde(default, rename = "type")]
+    pub field_type: RawFieldType,
+}
+
+/// The offset of an item number, e.g. 1 for `0042101` or `0042100001`.
+fn item_offset(item_number: &str) -> Option<usize> {
+    item_number
+        .trim()
+        .strip_prefix(ITEM_NUMBER_PREFIX)?
+        .parse()
+        .ok()
+}
+
+impl RawFieldDefinition {
+    /// The offsets of the first and last item of the field's item range.
+    fn item_range(&self) -> Option<(usize, Option<usize>)> {
+        match self.item_number.split_once('-') {
+            Some((first, last)) => Some((item_offset(first)?, Some(item_offset(last)?))),
+            None => Some((item_offset(&self.item_number)?, None)),
+        }
+    }
+
+    /// Where the field starts in the sport's data, given where its first item
+    /// starts. Offsets come from profiles and sport files, so they're checked
+    /// rather than trusted not to overflow.
+    fn start(&self, first: usize) -> Option<usize> {
+        first.checked_add(self.offset.saturating_sub(1))
+    }
+
+    /// Where the field starts in the sport's data and how long it is, or
+    /// `None` if it runs to the end of a single item, whose length depends on
+    /// the item's text, or past the last offset.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        let (first, last) = self.item_range()?;
+        let start = self.start(first)?;
+        let length = match (self.length, last) {
+            (0, Some(last)) => last.checked_add(1)?.checked_sub(start)?,
+            (0, None) => return None,
+            (length, _) => length,
+        };
+        // past the last offset, the simulator would have nowhere to write it
+        (start.checked_add(length)? <= MAX_OFFSET + 1).then_some((start, length))
+    }
+
+    /// Checks that the field is within the sport's data, for fields read from
+    /// files.
+    pub fn validate(&self) -> Result<(), String> {
+        let in_data = |offset: usize| (1..=MAX_OFFSET).contains(&offset);
+        let (first, last) = self.item_range().ok_or_else(|| {
+            format!(
+                "the field {} has an invalid item number {}",
+                self.name, self.item_number
+            )
+        })?;
+        let start = self.start(first).filter(|start| in_data(*start));
+        let end = match (start, self.length) {
+            (_, 0) => last.or(start),
+            (Some(start), length) => start.checked_add(length - 1),
+            (None, _) => None,
+        };
+        if start.is_none() || !end.is_some_and(in_data) {
+            return Err(format!(
+                "the field {} doesn't fit in offsets 1 to {}",
+                self.name, MAX_OFFSET
+            ));
+        }
+        Ok(())
+    }
+
+    /// The field's value, or `None` if that part of the sport's data hasn't
+    /// been received yet.
+    fn read(&self, items: &RawItemsState) -> Option<Value> {
+        let (first, last) = self.item_range()?;
+        let start = self.start(first)?;
+        let length = match (self.length, last) {
+            (0, Some(last)) => last.checked_add(1)?.checked_sub(start)?,
+            (0, None) => items.items.get(&first)?.chars().count().checked_sub(start - first)?,
+            (length, _) => length,
+        };
+        items.read(start, length).map(|text| self.field_type.parse(&text))
+    }
+}
+
+#[derive(Debug, Default)]
+struct RawItemsState {
+    /// The latest text of each item, by offset
+    items: BTreeMap<usize, String>,
+    /// The sport's data as written by every item so far, from offset 1
+    data: Vec<Option<char>>,
+}
+
+impl RawItemsState {
+    fn read(&self, start: usize, length: usize) -> Option<String> {
+        let start = start.checked_sub(1)?;
+        self.data
+            .get(start..start.checked_add(length)?)?
+            .iter()
+            .copied()
+            .collect()
+    }
+}
+
+/// The items read by a stream, shared between the data source that reads the
+/// frames and the raw sport that serializes them.
+#[derive(Debug, Clone, Default)]
+pub struct RawItems(Arc<Mutex<RawItemsState>>);
+
+impl RawItems {
+    pub fn observe(&self, frame: &[u8]) {
+        let Some((offset, text)) = frame_text(frame) else {
+            return;
+        };
+        let Some(start) = offset.checked_sub(1) else {
+            return;
+        };
+        let mut state = self.0.lock().expect("raw items lock poisoned");
+        let Some(end) = start.checked_add(text.chars().count()) else {
+            return;
+        };
+        if state.data.len() < end {
+            state.data.resize(end, None);
+        }
+        for (i, char) in text.chars().enumerate() {
+            state.data[start + i] = Some(char);
+        }
+        state.items.insert(offset, text);
+    }
+}
+
+pub struct RawSport<DS: RTDStateDataSource> {
+    rtd_state: RTDState<DS>,
+    items: RawItems,
+    fields: Vec<RawFieldDefinition>,
+    /// Whether every item is serialized as well as the named fields
+    include_items: bool,
+}
+
+impl<DS: RTDStateDataSource> RawSport<DS> {
+    pub fn new(rtd_state: RTDState<DS>) -> Self {
+        Self {
+            rtd_state,
+            items: RawItems::default(),
+            fields: vec![],
+            include_items: true,
+        }
+    }
+
+    /// Only serializes the named fields, for sports with a known layout.
+    pub fn without_items(mut self) -> Self {
+        self.include_items = false;
+        self
+    }
+
+    /// Reads items from `items`, which should be observing the frames of the
+    /// data source, and adds the named `fields`.
+    pub fn with_items(mut self, items: RawItems, fields: Vec<RawFieldDefinition>) -> Self {
+        self.items = items;
+        self.fields = fields;
+        self
+    }
+}
+
+impl<DS: RTDStateDataSource> Sport<DS> for RawSport<DS> {
+    fn name(&self) -> &'static str {
+        "Raw RTD"
+    }
+
+    fn rtd_state(&mut self) -> &mut RTDState<DS> {
+        &mut self.rtd_state
+    }
+}
+
+impl<DS: RTDStateDataSource> Serialize for RawSport<DS> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let state = self.items.0.lock().expect("raw items lock poisoned");
+        let items = self.include_items.then_some(&state.items);
+        let mut map = serializer
+            .serialize_map(Some(self.fields.len() + items.map_or(0, BTreeMap::len)))?;
+        for field in &self.fields {
+            // null until the item arrives, like the other sports' fields
+            map.serialize_entry(&field.name, &field.read(&state))?;
+        }
+        for (offset, text) in items.into_iter().flatten() {
+            map.serialize_entry(
+                &format!("{RAW_ITEM_KEY_PREFIX}{}", item_number(*offset)),
+                text,
+            )?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(offset: usize, length: usize) -> RawFieldDefinition {
+        RawFieldDefinition {
+            name: "clock".to_owned(),
+            item_number: "0042101".to_owned(),
+            offset,
+            length,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn checks_fields_fit_in_the_data() {
+        assert!(field(1, 5).validate().is_ok());
+        assert!(field(MAX_OFFSET, 0).validate().is_ok());
+        for field in [
+            field(usize::MAX, 2),
+            field(1, usize::MAX),
+            field(2, MAX_OFFSET),
+            RawFieldDefinition {
+                item_number: "0042101-0042100000".to_owned(),
+                ..field(1, 0)
+            },
+            RawFieldDefinition {
+                item_number: "lane 1".to_owned(),
+                ..field(1, 0)
+            },
+        ] {
+            assert!(field.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn huge_offsets_and_lengths_read_nothing() {
+        let mut items = RawItemsState::default();
+        items.items.insert(1, "12:00".to_owned());
+        items.data = "12:00".chars().map(Some).collect();
+        assert_eq!(field(1, 2).read(&items), Some(Value::from("12")));
+        for field in [field(usize::MAX, 2), field(1, usize::MAX), field(2, usize::MAX)] {
+            assert_eq!(field.span(), None);
+            assert_eq!(field.read(&items), None);
+        }
+    }
+}
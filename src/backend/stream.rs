@@ -1,13 +1,26 @@
+pub mod frame;
 pub mod latency_graph;
+pub mod replay;
+pub mod serial;
+pub mod source;
 
 use std::{
+    collections::{BTreeMap, HashMap},
     error::Error,
-    sync::Arc,
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use daktronics_allsport_5000::{sports::Sport, RTDState};
 use latency_graph::{LatencyGraphData, LatencySample, SerialEvent};
+use replay::ReplayControl;
+use serde_json::Value;
+use source::{ConnectionState, StreamDataSource, StreamInput};
 use tokio::{
     select,
     sync::{
@@ -16,13 +29,28 @@ use tokio::{
     },
     task::JoinHandle,
 };
-use tokio_serial::SerialPortBuilderExt;
 
-use crate::{backend::serializer::serialize_mappings, APP_USER_AGENT};
+use crate::{
+    backend::{
+        mapping::MappingState,
+        serializer::{serialize_mappings, DataStatus},
+    },
+    APP_USER_AGENT,
+};
 
-use super::{network::put_to_server, profile::Profile};
+use super::{
+    capture::{CaptureHeader, Recorder},
+    network::put_to_server,
+    profile::Profile,
+    sports::{
+        detection::{SportDetection, SportDetector},
+        raw::RawItems,
+        DynamicSportType,
+    },
+};
 
-const MAX_SERIAL_PACKET_DELAY: u64 = 3000;
+/// How often to check the configured sport against the packets received.
+const SPORT_DETECTION_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 pub struct ErrorInfo {
@@ -44,6 +72,22 @@ pub enum WorkerEvent {
     ErrorEvent(ErrorInfo),
     SerialEvent(SerialEvent),
     LatencySampleEvent(LatencySample, Option<String>, usize),
+    /// What the packets received say about the sport, sent when it first
+    /// becomes clear and whenever it changes
+    SportDetectionEvent(SportDetection),
+    /// The input was disconnected or reconnected
+    ConnectionEvent(ConnectionState, Instant),
+    /// The sport's fields as they were serialized when new data arrived
+    RawFieldsEvent(Value, Instant),
+}
+
+/// A field as serialized by the sport, for inspecting what the console sent
+/// before it's mapped.
+#[derive(Debug, Clone)]
+pub struct RawField {
+    pub value: Value,
+    /// When the value last changed
+    pub changed: Instant,
 }
 
 #[derive(Debug)]
@@ -53,45 +97,70 @@ pub struct ActiveStream {
     latest_payload: Option<String>,
     latest_payload_size: Option<usize>,
     errors: Vec<ErrorInfo>,
+    sport_type: Option<DynamicSportType>,
+    sport_detection: Option<SportDetection>,
+    /// The state of the input and when it last changed
+    connection_state: (ConnectionState, Instant),
+    raw_fields: BTreeMap<String, RawField>,
+    input_description: String,
+    recorder: Recorder,
+    replay_control: Option<ReplayControl>,
 
     serial_join_handle: JoinHandle<()>,
     network_processing_join_handle: JoinHandle<()>,
+    sport_detection_join_handle: JoinHandle<()>,
     pub(crate) worker_event_rx: Arc<Mutex<Receiver<WorkerEvent>>>,
 }
 
 impl ActiveStream {
-    pub fn new(profile: Profile, tty_path: String) -> Result<Self, Box<dyn Error>> {
+    pub fn new(
+        profile: Profile,
+        input: StreamInput,
+        recorder: Recorder,
+    ) -> Result<Self, Box<dyn Error>> {
         log::info!(
             target: "stream",
             "Creating stream bound to {} with profile {}",
-            tty_path,
+            input,
             profile.name
         );
         let (worker_event_tx, worker_event_rx) = mpsc::channel(256);
 
-        // allow because cargo gets suspicious on Windows
-        #[allow(unused_mut)]
-        let mut port = tokio_serial::new(tty_path, 19200)
-            .parity(tokio_serial::Parity::None)
-            .open_native_async()?;
-
-        #[cfg(unix)]
-        port.set_exclusive(false)
-            .expect("unable to set serial port exclusive to false");
-
-        let rtd_state = RTDState::from_serial_stream(port, true)?;
+        let input_description = input.to_string();
+        let step_by_step = input.is_step_by_step();
+        let stale_data_timeout_ms = profile.stale_data_timeout_ms;
+        let packet_timeout = (!step_by_step && stale_data_timeout_ms > 0)
+            .then_some(Duration::from_millis(stale_data_timeout_ms));
+        let sport_detector = SportDetector::default();
+        let raw_items = RawItems::default();
+        let data_source = StreamDataSource::open(input, profile.serial)?
+            .with_recorder(recorder.clone())
+            .with_sport_detector(sport_detector.clone())
+            .with_raw_items(raw_items.clone());
+        let replay_control = data_source
+            .replay_control()
+            .filter(|_| step_by_step);
+        let mut connection_state = data_source.connection_state();
+        let initial_connection_state = connection_state.borrow().clone();
+        let rtd_state = RTDState::new(data_source);
 
         let serialized = Arc::new(Mutex::new(None));
-        let mut sport = profile
+        // set when no data has arrived within the packet timeout
+        let stale = Arc::new(AtomicBool::new(false));
+        let sport_type = profile
             .sport_type
-            .ok_or("You must specify a sport before streaming.")?
-            .as_dynamic_sport(rtd_state);
+            .ok_or("You must specify a sport before streaming.")?;
+        let mut sport = sport_type
+            .as_dynamic_sport(rtd_state)
+            .with_raw_items(raw_items, profile.sport_fields().to_vec());
 
         let (new_msg_tx, mut new_msg_rx) = mpsc::unbounded_channel();
 
         let serial_join_handle = {
             let serialized = serialized.clone();
+            let stale = stale.clone();
             let worker_event_tx = worker_event_tx.clone();
+            let input_description = input_description.clone();
             tokio::task::spawn(async move {
                 loop {
                     // get the underlying rtd_state to update it
@@ -99,22 +168,65 @@ impl ActiveStream {
                         result = sport.rtd_state().update_async() => {
                             result.map_err(Some)
                         },
-                        () = tokio::time::sleep(Duration::from_millis(MAX_SERIAL_PACKET_DELAY)) => {
+                        () = async {
+                            match packet_timeout {
+                                Some(packet_timeout) => tokio::time::sleep(packet_timeout).await,
+                                None => std::future::pending().await,
+                            }
+                        } => {
+                            if stale.swap(true, Ordering::Relaxed) {
+                                // already reported when the data went stale
+                                continue;
+                            }
+                            log::warn!(
+                                target: "stream",
+                                "No new score data from {} in {} ms, marking the payload stale",
+                                input_description,
+                                stale_data_timeout_ms
+                            );
+                            // resend the last payload with the stale status
+                            let _ = new_msg_tx.send(());
+                            if matches!(*connection_state.borrow(), ConnectionState::Disconnected(_)) {
+                                // already reported, and the data source is reconnecting
+                                continue;
+                            }
                             Err(None)
+                        },
+                        Ok(()) = connection_state.changed() => {
+                            let state = connection_state.borrow_and_update().clone();
+                            worker_event_tx
+                                .send(WorkerEvent::ConnectionEvent(state, Instant::now()))
+                                .await
+                                .expect("worker event tx closed!");
+                            continue;
                         }
                     };
 
+                    if has_new_data.is_ok() && stale.swap(false, Ordering::Relaxed) {
+                        log::info!(
+                            target: "stream",
+                            "Score data from {} resumed, marking the payload live",
+                            input_description
+                        );
+                        // resend the last payload with the live status, even if nothing changed
+                        let _ = new_msg_tx.send(());
+                    }
+
                     match has_new_data {
                         Ok(true) => match sport.serialize_to_value() {
                             Ok(new_data) => {
+                                let raw_fields = new_data.clone();
                                 {
                                     let mut serialized = serialized.lock().await;
                                     *serialized = Some(new_data);
                                 }
+                                let timestamp = Instant::now();
                                 worker_event_tx
-                                    .send(WorkerEvent::SerialEvent(SerialEvent {
-                                        timestamp: Instant::now(),
-                                    }))
+                                    .send(WorkerEvent::SerialEvent(SerialEvent { timestamp }))
+                                    .await
+                                    .expect("worker event tx closed!");
+                                worker_event_tx
+                                    .send(WorkerEvent::RawFieldsEvent(raw_fields, timestamp))
                                     .await
                                     .expect("worker event tx closed!");
                                 if let Err(err) = new_msg_tx.send(()) {
@@ -143,7 +255,7 @@ impl ActiveStream {
                             .await
                             .expect("worker event tx closed!"),
                         Err(None) => worker_event_tx
-                            .send(WorkerEvent::ErrorEvent("timeout when waiting for new score data from the serial connection".to_owned().into()))
+                            .send(WorkerEvent::ErrorEvent(format!("timeout when waiting for new score data from {input_description}, the payload is now marked stale").into()))
                             .await
                             .expect("worker event tx closed!"),
                     }
@@ -173,19 +285,44 @@ impl ActiveStream {
                         .expect("worker event tx closed!")
                 }
 
+                let mut last_value = None;
+                let mut last_stale = false;
+                let mut mapping_states: HashMap<String, MappingState> = HashMap::new();
+                let mut pulse_ended = false;
+                let mut mapping_failed = false;
                 loop {
-                    let serialized = { serialized.lock().await.take() };
+                    let new_value = { serialized.lock().await.take() };
+                    let is_stale = stale.load(Ordering::Relaxed);
+                    let status_changed = is_stale != last_stale;
+                    last_stale = is_stale;
+                    let has_new_value = new_value.is_some();
+                    if has_new_value {
+                        last_value = new_value;
+                    }
+                    // a status change or a pulse ending resends the last payload so the composition hears about it
+                    let serialized = last_value
+                        .as_ref()
+                        .filter(|_| has_new_value || status_changed || pulse_ended);
+                    let status = if is_stale {
+                        DataStatus::Stale
+                    } else {
+                        DataStatus::Live
+                    };
                     let timestamp = SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .expect("what are you doing with your clock set so early?!")
                         .as_millis();
                     if let Some(value) = serialized {
-                        match serialize_mappings(
+                        let mapped = serialize_mappings(
                             &mappings,
-                            &value,
+                            value,
                             profile.exclude_incomplete_data,
                             Some(timestamp as i64),
-                        ) {
+                            Some(status),
+                            &mut mapping_states,
+                        );
+                        mapping_failed = mapped.is_err();
+                        match mapped {
                             Ok(serialized) => {
                                 let stringified = serialized.to_string();
                                 let stringified_bytes = stringified.as_bytes().len();
@@ -256,9 +393,26 @@ impl ActiveStream {
                                 .expect("worker event tx closed!"),
                         }
                     }
+                    // mapping again won't turn pulses off if it fails, so wait for new data
+                    let next_pulse_end = mapping_states
+                        .values()
+                        .filter_map(|state| state.changes.next_pulse_end())
+                        .min()
+                        .filter(|_| !mapping_failed);
+                    pulse_ended = false;
                     if new_msg_rx.is_empty() {
-                        // if it's empty right now, wait for the next signal
-                        new_msg_rx.recv().await;
+                        // if it's empty right now, wait for the next signal or pulse to end
+                        match next_pulse_end {
+                            Some(pulse_end) => select! {
+                                _ = new_msg_rx.recv() => {}
+                                _ = tokio::time::sleep_until(pulse_end.into()) => {
+                                    pulse_ended = true;
+                                }
+                            },
+                            None => {
+                                new_msg_rx.recv().await;
+                            }
+                        }
                     }
                     // flush the signal stream and go again
                     while !new_msg_rx.is_empty() {
@@ -268,6 +422,55 @@ impl ActiveStream {
             })
         };
 
+        let sport_detection_join_handle = {
+            let worker_event_tx = worker_event_tx.clone();
+            tokio::task::spawn(async move {
+                if !sport_type.is_detectable() {
+                    // there's nothing to compare the packets to
+                    return;
+                }
+                let mut last_detection: Option<SportDetection> = None;
+                loop {
+                    tokio::time::sleep(SPORT_DETECTION_INTERVAL).await;
+                    let Some(detection) = sport_detector.detect().await else {
+                        continue;
+                    };
+                    let changed = match last_detection {
+                        Some(ref last) => {
+                            last.best() != detection.best()
+                                || last.matches(sport_type) != detection.matches(sport_type)
+                        }
+                        None => true,
+                    };
+                    if !changed {
+                        continue;
+                    }
+                    if detection.matches(sport_type) {
+                        log::info!(
+                            target: "stream",
+                            "Packets from the console match the {} sport type",
+                            sport_type
+                        );
+                    } else {
+                        log::warn!(
+                            target: "stream",
+                            "Packets from the console look like {} rather than {}",
+                            detection
+                                .best()
+                                .map(|best| best.to_string())
+                                .unwrap_or_else(|| "no known sport".to_owned()),
+                            sport_type
+                        );
+                    }
+                    last_detection = Some(detection.clone());
+                    worker_event_tx
+                        .send(WorkerEvent::SportDetectionEvent(detection))
+                        .await
+                        .expect("worker event tx closed!");
+                }
+            })
+        };
+
         Ok(Self {
             latency_graph_data: LatencyGraphData {
                 samples: vec![],
@@ -276,65 +479,85 @@ impl ActiveStream {
             latest_payload: None,
             latest_payload_size: None,
             errors: vec![],
+            sport_type: profile.sport_type,
+            sport_detection: None,
+            connection_state: (initial_connection_state, Instant::now()),
+            raw_fields: BTreeMap::new(),
+            input_description,
+            recorder,
+            replay_control,
             serial_join_handle,
             network_processing_join_handle,
+            sport_detection_join_handle,
             worker_event_rx: Arc::new(Mutex::new(worker_event_rx)),
         })
     }
 
-    fn update_from_event(
-        errors: &mut Vec<ErrorInfo>,
-        latest_payload: &mut Option<String>,
-        latest_payload_size: &mut Option<usize>,
-        latency_graph_data: &mut LatencyGraphData,
-        event: WorkerEvent,
-    ) {
+    fn update_from_event(&mut self, event: WorkerEvent) {
         match event {
             WorkerEvent::ErrorEvent(err) => {
                 log::warn!(target: "stream", "Stream error: {}", err.msg);
-                errors.push(err)
+                self.errors.push(err)
             }
             WorkerEvent::LatencySampleEvent(
                 sample,
                 new_latest_payload,
                 new_latest_payload_size,
             ) => {
-                *latest_payload = new_latest_payload;
-                *latest_payload_size = Some(new_latest_payload_size);
-                latency_graph_data.samples.push(sample)
+                self.latest_payload = new_latest_payload;
+                self.latest_payload_size = Some(new_latest_payload_size);
+                self.latency_graph_data.samples.push(sample)
+            }
+            WorkerEvent::SerialEvent(event) => self.latency_graph_data.serial_events.push(event),
+            WorkerEvent::RawFieldsEvent(fields, timestamp) => {
+                if let Value::Object(fields) = fields {
+                    for (key, value) in fields {
+                        match self.raw_fields.get_mut(&key) {
+                            Some(field) if field.value == value => {}
+                            Some(field) => {
+                                field.value = value;
+                                field.changed = timestamp;
+                            }
+                            None => {
+                                self.raw_fields.insert(
+                                    key,
+                                    RawField {
+                                        value,
+                                        changed: timestamp,
+                                    },
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            WorkerEvent::SportDetectionEvent(detection) => self.sport_detection = Some(detection),
+            WorkerEvent::ConnectionEvent(state, timestamp) => {
+                if let ConnectionState::Reconnected(ref input) = state {
+                    log::info!(target: "stream", "Reconnected to {}", input);
+                }
+                self.connection_state = (state, timestamp)
             }
-            WorkerEvent::SerialEvent(event) => latency_graph_data.serial_events.push(event),
         }
     }
 
     pub async fn update_stats(&mut self) {
-        {
+        let events = {
             let mut rx = self.worker_event_rx.lock().await;
+            let mut events = Vec::new();
             while !rx.is_empty() {
-                let event = rx.recv().await;
-                if let Some(event) = event {
-                    ActiveStream::update_from_event(
-                        &mut self.errors,
-                        &mut self.latest_payload,
-                        &mut self.latest_payload_size,
-                        &mut self.latency_graph_data,
-                        event,
-                    );
+                if let Some(event) = rx.recv().await {
+                    events.push(event);
                 }
             }
-        }
-        self.purge_old_data(Duration::from_secs(60 * 5), 20)
+            events
+        };
+        self.update_from_events(events)
     }
 
     pub fn update_from_events(&mut self, events: Vec<WorkerEvent>) {
         for event in events {
-            ActiveStream::update_from_event(
-                &mut self.errors,
-                &mut self.latest_payload,
-                &mut self.latest_payload_size,
-                &mut self.latency_graph_data,
-                event,
-            );
+            self.update_from_event(event);
         }
         self.purge_old_data(Duration::from_secs(60 * 5), 20)
     }
@@ -361,6 +584,39 @@ impl ActiveStream {
         self.latest_payload.as_ref().map(String::as_str)
     }
 
+    /// Every field the sport serialized, as of the latest data from the
+    /// console.
+    pub fn raw_fields(&self) -> &BTreeMap<String, RawField> {
+        &self.raw_fields
+    }
+
+    /// The raw fields as pretty-printed JSON, for copying.
+    pub fn raw_fields_snapshot(&self) -> String {
+        serde_json::to_string_pretty(
+            &self
+                .raw_fields
+                .iter()
+                .map(|(key, field)| (key.clone(), field.value.clone()))
+                .collect::<serde_json::Map<_, _>>(),
+        )
+        .expect("json values always serialize")
+    }
+
+    /// The sport the packets received look like, if it isn't the profile's
+    /// sport type.
+    pub fn sport_mismatch(&self) -> Option<DynamicSportType> {
+        let detection = self.sport_detection.as_ref()?;
+        let sport_type = self.sport_type.filter(DynamicSportType::is_detectable)?;
+        (!detection.matches(sport_type))
+            .then(|| detection.best())
+            .flatten()
+    }
+
+    /// Whether the input is connected, and since when.
+    pub fn connection_state(&self) -> &(ConnectionState, Instant) {
+        &self.connection_state
+    }
+
     pub fn errors(&self) -> &[ErrorInfo] {
         &self.errors
     }
@@ -369,6 +625,31 @@ impl ActiveStream {
         self.errors.clear()
     }
 
+    /// The handle to step through the capture, if this stream is a
+    /// step-by-step replay.
+    pub fn replay_control(&self) -> Option<&ReplayControl> {
+        self.replay_control.as_ref()
+    }
+
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    /// Starts recording raw RTD traffic to a capture file at the given path,
+    /// replacing any capture already being recorded.
+    pub fn start_recording(&self, path: &Path) -> io::Result<()> {
+        self.recorder.start(
+            path,
+            &CaptureHeader::new(self.sport_type, self.input_description.clone()),
+        )
+    }
+
+    /// Whether the stream has collected more errors than unattended mode
+    /// tolerates and should be restarted.
+    pub fn should_restart(&self, unattended: Option<usize>) -> bool {
+        unattended.is_some_and(|max_errors| self.errors.len() > max_errors)
+    }
+
     /// Deletes all latency graph data with ages more than the specific duration
     pub fn purge_old_data(&mut self, keep_graph: Duration, keep_errors: usize) {
         self.latency_graph_data
@@ -387,5 +668,6 @@ impl Drop for ActiveStream {
     fn drop(&mut self) {
         self.serial_join_handle.abort();
         self.network_processing_join_handle.abort();
+        self.sport_detection_join_handle.abort();
     }
 }
@@ -0,0 +1,124 @@
+//! Framing of the RTD protocol spoken by the AllSport 5000.
+//!
+//! Each frame looks like `SYN SOH <header> STX <text> EOT <checksum> ETB`. The
+//! header is the item number, `004210` followed by the four-digit offset of
+//! the text in the sport's data.
+
+const SYN: u8 = 0x16;
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ETB: u8 = 0x17;
+
+pub const ITEM_NUMBER_PREFIX: &str = "004210";
+/// The highest offset a header can hold, so no item reaches past it.
+pub const MAX_OFFSET: usize = 9999;
+
+/// Pops the next complete RTD frame (`SYN` through `ETB`) off the front of the
+/// buffer, discarding any line noise before it.
+pub fn take_frame(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let end = buffer.iter().position(|byte| *byte == ETB)?;
+    let mut frame = buffer.drain(..=end).collect::<Vec<_>>();
+    if let Some(start) = frame.iter().rposition(|byte| *byte == SYN) {
+        frame.drain(..start);
+    }
+    Some(frame)
+}
+
+/// The most bytes kept while waiting for a frame to end. Frames are a few
+/// hundred bytes, so more than this without an `ETB` means the stream isn't
+/// RTD, e.g. when the baud rate is wrong.
+pub const MAX_BUFFERED_BYTES: usize = 4096;
+
+/// Drops bytes off the front of a buffer with no complete frame until it's no
+/// longer than [`MAX_BUFFERED_BYTES`], keeping from a `SYN` that could start a
+/// frame. Returns how many bytes were dropped.
+pub fn drop_overflow(buffer: &mut Vec<u8>) -> usize {
+    if buffer.len() <= MAX_BUFFERED_BYTES {
+        return 0;
+    }
+    let earliest = buffer.len() - MAX_BUFFERED_BYTES;
+    let end = buffer[earliest..]
+        .iter()
+        .position(|byte| *byte == SYN)
+        .map_or(buffer.len(), |start| earliest + start);
+    buffer.drain(..end);
+    end
+}
+
+/// Builds a frame that writes `text` at `offset` in the sport's data.
+pub fn encode_frame(offset: usize, text: &str) -> Vec<u8> {
+    let mut frame = vec![SYN, SOH];
+    frame.extend_from_slice(format!("{}{:04}", ITEM_NUMBER_PREFIX, offset).as_bytes());
+    frame.push(STX);
+    frame.extend_from_slice(text.as_bytes());
+    frame.push(EOT);
+    // the checksum is the sum of everything from SOH through EOT, as hex
+    let checksum = frame[1..]
+        .iter()
+        .fold(0u8, |checksum, byte| checksum.wrapping_add(*byte));
+    frame.extend_from_slice(format!("{:02X}", checksum).as_bytes());
+    frame.push(ETB);
+    frame
+}
+
+/// The item number of the frame writing at `offset`, as used to name raw
+/// fields, e.g. `0042101` for offset 1.
+pub fn item_number(offset: usize) -> String {
+    format!("{}{}", ITEM_NUMBER_PREFIX, offset)
+}
+
+/// The offset and text of a frame, from its header. Returns `None` if the
+/// frame isn't laid out like an RTD frame.
+pub fn frame_text(frame: &[u8]) -> Option<(usize, String)> {
+    let (offset, _) = frame_span(frame)?;
+    let stx = frame.iter().position(|byte| *byte == STX)?;
+    let eot = frame.iter().rposition(|byte| *byte == EOT)?;
+    Some((offset, String::from_utf8_lossy(&frame[stx + 1..eot]).into_owned()))
+}
+
+/// The offset and text length of a frame, from its header. Returns `None` if
+/// the frame isn't laid out like an RTD frame.
+pub fn frame_span(frame: &[u8]) -> Option<(usize, usize)> {
+    let soh = frame.iter().position(|byte| *byte == SOH)?;
+    let stx = frame.iter().position(|byte| *byte == STX)?;
+    let eot = frame.iter().rposition(|byte| *byte == EOT)?;
+    if !(soh < stx && stx < eot) {
+        return None;
+    }
+    let header = std::str::from_utf8(&frame[soh + 1..stx]).ok()?;
+    let offset = header.strip_prefix(ITEM_NUMBER_PREFIX)?.parse().ok()?;
+    Some((offset, eot - stx - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_buffers_with_room() {
+        let mut buffer = vec![b'x'; MAX_BUFFERED_BYTES];
+        assert_eq!(drop_overflow(&mut buffer), 0);
+        assert_eq!(buffer.len(), MAX_BUFFERED_BYTES);
+    }
+
+    #[test]
+    fn drops_overflow_up_to_the_next_syn() {
+        let frame = encode_frame(1, "12:00");
+        let mut buffer = vec![SYN; 10];
+        buffer.extend(vec![b'x'; MAX_BUFFERED_BYTES]);
+        buffer.extend(&frame[..frame.len() - 1]);
+        let length = buffer.len();
+        let dropped = drop_overflow(&mut buffer);
+        assert_eq!(dropped, length - (frame.len() - 1));
+        buffer.push(ETB);
+        assert_eq!(take_frame(&mut buffer), Some(frame));
+    }
+
+    #[test]
+    fn drops_everything_without_a_syn() {
+        let mut buffer = vec![b'x'; MAX_BUFFERED_BYTES + 1];
+        assert_eq!(drop_overflow(&mut buffer), MAX_BUFFERED_BYTES + 1);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+
+use tokio::sync::Notify;
+
+use crate::backend::capture::{Capture, CaptureRecord};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaySpeed {
+    RealTime,
+    Double,
+    Ten,
+    /// Only advance one packet at a time when [`ReplayControl::step`] is called
+    Step,
+}
+
+impl ReplaySpeed {
+    pub const ALL: [ReplaySpeed; 4] = [
+        ReplaySpeed::RealTime,
+        ReplaySpeed::Double,
+        ReplaySpeed::Ten,
+        ReplaySpeed::Step,
+    ];
+
+    fn factor(&self) -> Option<u32> {
+        match self {
+            ReplaySpeed::RealTime => Some(1),
+            ReplaySpeed::Double => Some(2),
+            ReplaySpeed::Ten => Some(10),
+            ReplaySpeed::Step => None,
+        }
+    }
+}
+
+impl Display for ReplaySpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReplaySpeed::RealTime => "Real time",
+            ReplaySpeed::Double => "2x",
+            ReplaySpeed::Ten => "10x",
+            ReplaySpeed::Step => "Step by step",
+        })
+    }
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "1x" => Ok(ReplaySpeed::RealTime),
+            "2" | "2x" => Ok(ReplaySpeed::Double),
+            "10" | "10x" => Ok(ReplaySpeed::Ten),
+            "step" => Ok(ReplaySpeed::Step),
+            _ => Err(format!("unknown replay speed {}; use 1, 2, 10 or step", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayOptions {
+    pub path: PathBuf,
+    pub speed: ReplaySpeed,
+    pub looping: bool,
+}
+
+/// Lets the UI advance a step-by-step replay.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayControl(Arc<Notify>);
+
+impl ReplayControl {
+    /// Releases the next packet of a step-by-step replay.
+    pub fn step(&self) {
+        self.0.notify_one();
+    }
+}
+
+/// Plays back the frames of a capture with their original timing, scaled by
+/// the replay speed.
+#[derive(Debug)]
+pub struct Replay {
+    records: Vec<CaptureRecord>,
+    position: usize,
+    speed: ReplaySpeed,
+    looping: bool,
+    control: ReplayControl,
+    /// When the first record of the current pass was played
+    pass_started: Option<Instant>,
+}
+
+impl Replay {
+    pub fn new(capture: Capture, options: &ReplayOptions) -> Self {
+        log::info!(
+            target: "stream",
+            "Replaying {} packets recorded from {} with sport type {}",
+            capture.records.len(),
+            capture.header.port,
+            capture
+                .header
+                .sport_type
+                .map(|sport_type| sport_type.to_string())
+                .unwrap_or_else(|| "unset".to_owned())
+        );
+        Self {
+            records: capture.records,
+            position: 0,
+            speed: options.speed,
+            looping: options.looping,
+            control: ReplayControl::default(),
+            pass_started: None,
+        }
+    }
+
+    pub fn control(&self) -> &ReplayControl {
+        &self.control
+    }
+
+    /// Waits until the next frame is due and returns it.
+    ///
+    /// This is cancel-safe: the deadline for each frame is derived from when
+    /// the pass started, so retrying after a timeout doesn't skew playback.
+    pub async fn next_frame(&mut self) -> Vec<u8> {
+        if self.position >= self.records.len() {
+            if self.looping && !self.records.is_empty() {
+                log::info!(target: "stream", "Reached the end of the capture, looping");
+                self.position = 0;
+                self.pass_started = None;
+            } else {
+                if self.position == self.records.len() {
+                    log::info!(target: "stream", "Reached the end of the capture");
+                    // only log once
+                    self.position += 1;
+                }
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let first_timestamp = self.records[0].timestamp;
+        let record = &self.records[self.position];
+        match self.speed.factor() {
+            Some(factor) => {
+                let pass_started = *self.pass_started.get_or_insert_with(Instant::now);
+                // a capture edited out of order plays the early records
+                // straight away rather than panicking
+                let due = pass_started + record.timestamp.saturating_sub(first_timestamp) / factor;
+                tokio::time::sleep_until(due.into()).await;
+            }
+            None => self.control.0.notified().await,
+        }
+        self.position += 1;
+        record.frame.clone()
+    }
+}
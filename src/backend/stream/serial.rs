@@ -0,0 +1,443 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use daktronics_allsport_5000::packet::Packet;
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncReadExt, task::JoinSet};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+use super::frame::take_frame;
+
+/// Baud rates offered in the UI. Any other rate can still be set in the profile
+/// or on the command line.
+pub const COMMON_BAUD_RATES: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+
+/// The serial path that asks for the console's port to be detected instead.
+pub const AUTO_SERIAL_PATH: &str = "auto";
+
+/// How long to listen on each port when detecting which one the console is on.
+/// The console sends the clock several times a second, so a few seconds is
+/// plenty.
+const DETECTION_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+impl Parity {
+    pub const ALL: [Parity; 3] = [Parity::None, Parity::Odd, Parity::Even];
+}
+
+impl Display for Parity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Parity::None => "No parity",
+            Parity::Odd => "Odd parity",
+            Parity::Even => "Even parity",
+        })
+    }
+}
+
+impl FromStr for Parity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" | "n" => Ok(Parity::None),
+            "odd" | "o" => Ok(Parity::Odd),
+            "even" | "e" => Ok(Parity::Even),
+            _ => Err(format!("unknown parity {}; use none, odd or even", s)),
+        }
+    }
+}
+
+impl From<Parity> for tokio_serial::Parity {
+    fn from(value: Parity) -> Self {
+        match value {
+            Parity::None => tokio_serial::Parity::None,
+            Parity::Odd => tokio_serial::Parity::Odd,
+            Parity::Even => tokio_serial::Parity::Even,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+impl DataBits {
+    pub const ALL: [DataBits; 4] = [
+        DataBits::Five,
+        DataBits::Six,
+        DataBits::Seven,
+        DataBits::Eight,
+    ];
+}
+
+impl Display for DataBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DataBits::Five => "5 data bits",
+            DataBits::Six => "6 data bits",
+            DataBits::Seven => "7 data bits",
+            DataBits::Eight => "8 data bits",
+        })
+    }
+}
+
+impl FromStr for DataBits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "5" => Ok(DataBits::Five),
+            "6" => Ok(DataBits::Six),
+            "7" => Ok(DataBits::Seven),
+            "8" => Ok(DataBits::Eight),
+            _ => Err(format!("unsupported data bits {}; use 5, 6, 7 or 8", s)),
+        }
+    }
+}
+
+impl From<DataBits> for tokio_serial::DataBits {
+    fn from(value: DataBits) -> Self {
+        match value {
+            DataBits::Five => tokio_serial::DataBits::Five,
+            DataBits::Six => tokio_serial::DataBits::Six,
+            DataBits::Seven => tokio_serial::DataBits::Seven,
+            DataBits::Eight => tokio_serial::DataBits::Eight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+impl StopBits {
+    pub const ALL: [StopBits; 2] = [StopBits::One, StopBits::Two];
+}
+
+impl Display for StopBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StopBits::One => "1 stop bit",
+            StopBits::Two => "2 stop bits",
+        })
+    }
+}
+
+impl FromStr for StopBits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(StopBits::One),
+            "2" => Ok(StopBits::Two),
+            _ => Err(format!("unsupported stop bits {}; use 1 or 2", s)),
+        }
+    }
+}
+
+impl From<StopBits> for tokio_serial::StopBits {
+    fn from(value: StopBits) -> Self {
+        match value {
+            StopBits::One => tokio_serial::StopBits::One,
+            StopBits::Two => tokio_serial::StopBits::Two,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowControl {
+    None,
+    /// XON/XOFF
+    Software,
+    /// RTS/CTS
+    Hardware,
+}
+
+impl FlowControl {
+    pub const ALL: [FlowControl; 3] = [
+        FlowControl::None,
+        FlowControl::Software,
+        FlowControl::Hardware,
+    ];
+}
+
+impl Display for FlowControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FlowControl::None => "No flow control",
+            FlowControl::Software => "XON/XOFF",
+            FlowControl::Hardware => "RTS/CTS",
+        })
+    }
+}
+
+impl FromStr for FlowControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(FlowControl::None),
+            "software" | "xonxoff" => Ok(FlowControl::Software),
+            "hardware" | "rtscts" => Ok(FlowControl::Hardware),
+            _ => Err(format!(
+                "unknown flow control {}; use none, software or hardware",
+                s
+            )),
+        }
+    }
+}
+
+impl From<FlowControl> for tokio_serial::FlowControl {
+    fn from(value: FlowControl) -> Self {
+        match value {
+            FlowControl::None => tokio_serial::FlowControl::None,
+            FlowControl::Software => tokio_serial::FlowControl::Software,
+            FlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+        }
+    }
+}
+
+/// How to talk to the console's serial port. The defaults match the AllSport
+/// 5000's RTD output, 19200 8N1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub parity: Parity,
+    pub data_bits: DataBits,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self {
+            baud_rate: 19200,
+            parity: Parity::None,
+            data_bits: DataBits::Eight,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl Display for SerialSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}{}{}",
+            self.baud_rate,
+            match self.data_bits {
+                DataBits::Five => 5,
+                DataBits::Six => 6,
+                DataBits::Seven => 7,
+                DataBits::Eight => 8,
+            },
+            match self.parity {
+                Parity::None => 'N',
+                Parity::Odd => 'O',
+                Parity::Even => 'E',
+            },
+            match self.stop_bits {
+                StopBits::One => 1,
+                StopBits::Two => 2,
+            }
+        )?;
+        if self.flow_control != FlowControl::None {
+            write!(f, " {}", self.flow_control)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn open_serial(
+    tty_path: &str,
+    settings: &SerialSettings,
+) -> Result<SerialStream, tokio_serial::Error> {
+    // allow because cargo gets suspicious on Windows
+    #[allow(unused_mut)]
+    let mut port = tokio_serial::new(tty_path, settings.baud_rate)
+        .parity(settings.parity.into())
+        .data_bits(settings.data_bits.into())
+        .stop_bits(settings.stop_bits.into())
+        .flow_control(settings.flow_control.into())
+        .open_native_async()?;
+
+    // an error here is reported like a failure to open, so detection skips
+    // the port and reconnecting retries it
+    #[cfg(unix)]
+    port.set_exclusive(false)?;
+
+    Ok(port)
+}
+
+/// How many valid RTD packets arrived on a port while detecting the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortScore {
+    pub port_name: String,
+    pub valid_packets: usize,
+}
+
+/// Listens on a port for [`DETECTION_DURATION`] and collects the frames that
+/// parse as RTD packets. Returns `None` if the port couldn't be opened.
+async fn sniff_port(port_name: &str, settings: &SerialSettings) -> Option<Vec<Vec<u8>>> {
+    let mut port = match open_serial(port_name, settings) {
+        Ok(port) => port,
+        Err(err) => {
+            log::info!(target: "detect", "Skipping {}: {}", port_name, err);
+            return None;
+        }
+    };
+    let deadline = Instant::now() + DETECTION_DURATION;
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let mut valid_frames = Vec::new();
+    loop {
+        match tokio::time::timeout_at(deadline.into(), port.read(&mut chunk)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(n)) => buffer.extend_from_slice(&chunk[..n]),
+            Ok(Err(err)) => {
+                log::info!(target: "detect", "Stopped listening on {}: {}", port_name, err);
+                break;
+            }
+        }
+        while let Some(frame) = take_frame(&mut buffer) {
+            if Packet::try_from(&frame[..]).is_ok() {
+                valid_frames.push(frame);
+            }
+        }
+    }
+    log::info!(
+        target: "detect",
+        "Received {} valid packets on {}",
+        valid_frames.len(),
+        port_name
+    );
+    Some(valid_frames)
+}
+
+/// Listens on every serial port at once, returning the valid frames received
+/// on each port that could be opened, most frames first.
+async fn sniff_serial_ports(settings: SerialSettings) -> Vec<(String, Vec<Vec<u8>>)> {
+    let mut tasks = JoinSet::new();
+    for port in tokio_serial::available_ports().unwrap_or_default() {
+        tasks.spawn(async move {
+            sniff_port(&port.port_name, &settings)
+                .await
+                .map(|frames| (port.port_name, frames))
+        });
+    }
+    let mut ports = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        if let Ok(Some(port)) = result {
+            ports.push(port);
+        }
+    }
+    ports.sort_by(|(a_name, a_frames), (b_name, b_frames)| {
+        b_frames
+            .len()
+            .cmp(&a_frames.len())
+            .then_with(|| a_name.cmp(b_name))
+    });
+    ports
+}
+
+/// Ranks the serial ports by how many valid RTD packets arrived on each, most
+/// first. Ports that couldn't be opened are left out.
+pub async fn detect_serial_ports(settings: SerialSettings) -> Vec<PortScore> {
+    sniff_serial_ports(settings)
+        .await
+        .into_iter()
+        .map(|(port_name, frames)| PortScore {
+            port_name,
+            valid_packets: frames.len(),
+        })
+        .collect()
+}
+
+/// The port the console is most likely on, if any port received valid packets.
+pub async fn detect_serial_port(settings: SerialSettings) -> Option<String> {
+    sniff_console(settings).await.map(|(port_name, _)| port_name)
+}
+
+/// The port the console is most likely on and the frames it sent while
+/// listening, if any port received valid packets.
+pub async fn sniff_console(settings: SerialSettings) -> Option<(String, Vec<Vec<u8>>)> {
+    sniff_serial_ports(settings)
+        .await
+        .into_iter()
+        .next()
+        .filter(|(_, frames)| !frames.is_empty())
+}
+
+/// What identifies a USB serial adapter regardless of the path the OS gives
+/// it, so it can be found again after being unplugged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbIdentity {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+}
+
+impl UsbIdentity {
+    /// The identity of the adapter behind `port_name`, if it's a USB port.
+    pub fn of_port(port_name: &str) -> Option<Self> {
+        tokio_serial::available_ports()
+            .ok()?
+            .into_iter()
+            .find(|port| port.port_name == port_name)
+            .and_then(|port| match port.port_type {
+                tokio_serial::SerialPortType::UsbPort(info) => Some(Self {
+                    vid: info.vid,
+                    pid: info.pid,
+                    serial_number: info.serial_number,
+                }),
+                _ => None,
+            })
+    }
+
+    /// The path of the port the adapter is currently plugged in as, if any.
+    pub fn find_port(&self) -> Option<String> {
+        tokio_serial::available_ports()
+            .ok()?
+            .into_iter()
+            .find(|port| match port.port_type {
+                tokio_serial::SerialPortType::UsbPort(ref info) => {
+                    info.vid == self.vid
+                        && info.pid == self.pid
+                        && info.serial_number == self.serial_number
+                }
+                _ => false,
+            })
+            .map(|port| port.port_name)
+    }
+}
+
+impl Display for UsbIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vid, self.pid)?;
+        if let Some(ref serial_number) = self.serial_number {
+            write!(f, " ({})", serial_number)?;
+        }
+        Ok(())
+    }
+}
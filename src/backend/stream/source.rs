@@ -0,0 +1,472 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use daktronics_allsport_5000::{
+    packet::{Packet, PacketParseError},
+    rtd_state::data_source::RTDStateDataSource,
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpStream, UdpSocket},
+    sync::watch,
+};
+use tokio_serial::SerialStream;
+
+use crate::backend::{
+    capture::{read_capture, Recorder},
+    simulator::SimulatorFeed,
+    sports::{detection::SportDetector, raw::RawItems},
+};
+
+use super::{
+    frame::{drop_overflow, take_frame},
+    replay::{Replay, ReplayControl, ReplayOptions, ReplaySpeed},
+    serial::{open_serial, SerialSettings, UsbIdentity},
+};
+
+const TCP_SCHEME: &str = "tcp://";
+const UDP_SCHEME: &str = "udp://";
+
+/// How long to wait before the first attempt to reconnect after the
+/// connection failed or was closed. Each failed attempt doubles the wait, up
+/// to [`MAX_RECONNECT_DELAY`].
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Whether a stream is currently receiving from its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Trying to reach the input for the first time
+    Connecting,
+    Connected,
+    /// The connection was lost or couldn't be made, with the reason; the
+    /// stream keeps retrying in the background
+    Disconnected(String),
+    /// Connected again after being disconnected, to the given input (which
+    /// may differ from the original path if a USB adapter came back under a
+    /// new name)
+    Reconnected(String),
+}
+
+/// Where a stream reads RTD data from.
+#[derive(Debug, Clone)]
+pub enum StreamInput {
+    /// A local serial port, e.g. /dev/ttyUSB0 or COM1
+    Serial(String),
+    /// A serial device server that accepts TCP connections, as `host:port`
+    Tcp(String),
+    /// A local `address:port` to receive UDP datagrams from a serial device
+    /// server on
+    Udp(String),
+    /// A previously recorded capture file
+    Replay(ReplayOptions),
+    /// Frames synthesized in-process by the scoreboard simulator
+    Simulator(SimulatorFeed),
+}
+
+impl StreamInput {
+    /// Whether packets only arrive when the user asks for them, so going quiet
+    /// isn't a sign that something is wrong.
+    pub fn is_step_by_step(&self) -> bool {
+        matches!(
+            self,
+            StreamInput::Replay(ReplayOptions {
+                speed: ReplaySpeed::Step,
+                ..
+            })
+        )
+    }
+}
+
+impl Display for StreamInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamInput::Serial(path) => f.write_str(path),
+            StreamInput::Tcp(address) => write!(f, "{}{}", TCP_SCHEME, address),
+            StreamInput::Udp(address) => write!(f, "{}{}", UDP_SCHEME, address),
+            StreamInput::Replay(options) => write!(f, "replay of {}", options.path.display()),
+            StreamInput::Simulator(_) => f.write_str("simulator"),
+        }
+    }
+}
+
+impl FromStr for StreamInput {
+    type Err = String;
+
+    /// Parses `tcp://host:port` and `udp://address:port` as network sources
+    /// and anything else as a serial port path.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let network_address = |address: &str| {
+            if address.rsplit_once(':').is_some_and(|(host, port)| {
+                !host.is_empty() && port.parse::<u16>().is_ok()
+            }) {
+                Ok(address.to_owned())
+            } else {
+                Err(format!("expected host:port after the scheme in {}", s))
+            }
+        };
+        if let Some(address) = s.strip_prefix(TCP_SCHEME) {
+            Ok(StreamInput::Tcp(network_address(address)?))
+        } else if let Some(address) = s.strip_prefix(UDP_SCHEME) {
+            Ok(StreamInput::Udp(network_address(address)?))
+        } else if s.is_empty() {
+            Err("serial path cannot be empty".to_owned())
+        } else {
+            Ok(StreamInput::Serial(s.to_owned()))
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Connection {
+    Serial(SerialStream),
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Connection {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Serial(port) => match port.read(buf).await? {
+                0 => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "serial device disappeared",
+                )),
+                n => Ok(n),
+            },
+            Connection::Tcp(stream) => match stream.read(buf).await? {
+                0 => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed by the device server",
+                )),
+                n => Ok(n),
+            },
+            Connection::Udp(socket) => socket.recv(buf).await,
+        }
+    }
+}
+
+/// Reads RTD frames from any of the supported [`StreamInput`]s.
+///
+/// Serial ports and captures are opened immediately so a bad path is reported
+/// before the stream starts. Network sources are connected lazily. When a
+/// connection is lost, it's retried with backoff in the background rather than
+/// surfaced through `RTDState::update_async`; watch
+/// [`StreamDataSource::connection_state`] to find out about it instead.
+#[derive(Debug)]
+pub struct StreamDataSource {
+    input: StreamInput,
+    serial_settings: SerialSettings,
+    connection: Option<Connection>,
+    /// The USB adapter behind the serial port, to find it again if it comes
+    /// back under another path
+    usb_identity: Option<UsbIdentity>,
+    connection_state: watch::Sender<ConnectionState>,
+    replay: Option<Replay>,
+    last_connection_failure: Option<Instant>,
+    reconnect_delay: Duration,
+    buffer: Vec<u8>,
+    recorder: Recorder,
+    sport_detector: SportDetector,
+    raw_items: RawItems,
+}
+
+impl StreamDataSource {
+    /// Opens the input, using `serial_settings` if it's a serial port.
+    pub fn open(
+        input: StreamInput,
+        serial_settings: SerialSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut connection = None;
+        let mut usb_identity = None;
+        let mut replay = None;
+        match &input {
+            StreamInput::Serial(tty_path) => {
+                log::info!(target: "stream", "Opening {} at {}", tty_path, serial_settings);
+                connection = Some(Connection::Serial(open_serial(
+                    tty_path,
+                    &serial_settings,
+                )?));
+                usb_identity = UsbIdentity::of_port(tty_path);
+            }
+            StreamInput::Tcp(_) | StreamInput::Udp(_) | StreamInput::Simulator(_) => {}
+            StreamInput::Replay(options) => {
+                replay = Some(Replay::new(read_capture(&options.path)?, options))
+            }
+        }
+        let (connection_state, _) = watch::channel(match input {
+            StreamInput::Tcp(_) | StreamInput::Udp(_) => ConnectionState::Connecting,
+            _ => ConnectionState::Connected,
+        });
+        Ok(Self {
+            input,
+            serial_settings,
+            connection,
+            usb_identity,
+            connection_state,
+            replay,
+            last_connection_failure: None,
+            reconnect_delay: MIN_RECONNECT_DELAY,
+            buffer: Vec::new(),
+            recorder: Recorder::default(),
+            sport_detector: SportDetector::default(),
+            raw_items: RawItems::default(),
+        })
+    }
+
+    /// The handle to step through the capture, if this is a replay.
+    pub fn replay_control(&self) -> Option<ReplayControl> {
+        self.replay.as_ref().map(|replay| replay.control().clone())
+    }
+
+    /// Writes every frame read to the recorder's capture while it's recording.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Tallies every valid packet read so the sport can be detected.
+    pub fn with_sport_detector(mut self, sport_detector: SportDetector) -> Self {
+        self.sport_detector = sport_detector;
+        self
+    }
+
+    /// Keeps the text of every item read in `raw_items`, for the raw sport.
+    pub fn with_raw_items(mut self, raw_items: RawItems) -> Self {
+        self.raw_items = raw_items;
+        self
+    }
+
+    /// Follows whether the input is connected.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.connection_state.subscribe()
+    }
+
+    /// Where to reopen the serial port: the original path if it's there, or
+    /// wherever the same USB adapter is plugged in now.
+    fn serial_path_for_reconnect(&self, tty_path: &str) -> String {
+        let path_exists = tokio_serial::available_ports()
+            .map(|ports| ports.iter().any(|port| port.port_name == tty_path))
+            .unwrap_or(true);
+        if path_exists {
+            return tty_path.to_owned();
+        }
+        match self
+            .usb_identity
+            .as_ref()
+            .and_then(|identity| identity.find_port().map(|path| (identity, path)))
+        {
+            Some((identity, path)) => {
+                log::info!(
+                    target: "stream",
+                    "USB adapter {} moved from {} to {}",
+                    identity,
+                    tty_path,
+                    path
+                );
+                path
+            }
+            None => tty_path.to_owned(),
+        }
+    }
+
+    async fn connect(&mut self) -> io::Result<Connection> {
+        if let Some(last_failure) = self.last_connection_failure {
+            tokio::time::sleep(self.reconnect_delay.saturating_sub(last_failure.elapsed()))
+                .await;
+        }
+        if let StreamInput::Serial(ref tty_path) = self.input {
+            let path = self.serial_path_for_reconnect(tty_path);
+            self.input = StreamInput::Serial(path);
+        }
+        let connection = match &self.input {
+            StreamInput::Serial(tty_path) => open_serial(tty_path, &self.serial_settings)
+                .map(Connection::Serial)
+                .map_err(io::Error::from),
+            StreamInput::Tcp(address) => TcpStream::connect(address).await.map(Connection::Tcp),
+            StreamInput::Udp(address) => UdpSocket::bind(address).await.map(Connection::Udp),
+            StreamInput::Replay(_) | StreamInput::Simulator(_) => {
+                unreachable!("replays and simulators don't have a connection")
+            }
+        };
+        match connection {
+            Ok(connection) => {
+                log::info!(target: "stream", "Connected to {}", self.input);
+                self.connection_state.send_modify(|state| {
+                    *state = match state {
+                        ConnectionState::Disconnected(_) => {
+                            ConnectionState::Reconnected(self.input.to_string())
+                        }
+                        _ => ConnectionState::Connected,
+                    }
+                });
+                self.last_connection_failure = None;
+                self.reconnect_delay = MIN_RECONNECT_DELAY;
+                Ok(connection)
+            }
+            Err(err) => {
+                if self.last_connection_failure.is_some() {
+                    self.reconnect_delay = (self.reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+                self.last_connection_failure = Some(Instant::now());
+                Err(err)
+            }
+        }
+    }
+
+    /// Records that the connection is down, only notifying watchers the first
+    /// time so a long outage doesn't flood them.
+    fn disconnected(&self, err: &io::Error) {
+        let reason = err.to_string();
+        self.connection_state.send_if_modified(|state| {
+            if matches!(state, ConnectionState::Disconnected(_)) {
+                false
+            } else {
+                log::warn!(
+                    target: "stream",
+                    "Lost connection to {}, reconnecting: {}",
+                    self.input,
+                    reason
+                );
+                *state = ConnectionState::Disconnected(reason);
+                true
+            }
+        });
+    }
+
+    /// Reads until a complete frame is buffered, reconnecting as many times as
+    /// it takes if the connection is lost.
+    ///
+    /// This is cancel-safe: partial frames stay in the buffer if the future is
+    /// dropped, e.g. by the packet timeout, and the backoff is tracked from
+    /// the time of the last failure.
+    async fn read_frame(&mut self) -> Vec<u8> {
+        if let Some(ref mut replay) = self.replay {
+            return replay.next_frame().await;
+        }
+        if let StreamInput::Simulator(ref feed) = self.input {
+            return feed.recv().await;
+        }
+        let mut chunk = [0u8; 1024];
+        loop {
+            if let Some(frame) = take_frame(&mut self.buffer) {
+                return frame;
+            }
+            // an unfinished frame can't grow the buffer forever
+            let dropped = drop_overflow(&mut self.buffer);
+            if dropped > 0 {
+                log::warn!(
+                    target: "stream",
+                    "Dropped {} bytes from {} that weren't RTD frames; check its settings",
+                    dropped,
+                    self.input
+                );
+            }
+            if self.connection.is_none() {
+                match self.connect().await {
+                    Ok(connection) => self.connection = Some(connection),
+                    Err(err) => {
+                        log::debug!(target: "stream", "Couldn't connect to {}: {}", self.input, err);
+                        self.disconnected(&err);
+                        continue;
+                    }
+                }
+            }
+            let connection = self
+                .connection
+                .as_mut()
+                .expect("connection should be open after connecting");
+            match connection.read(&mut chunk).await {
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) => {
+                    // drop the connection so the next pass reconnects
+                    self.connection = None;
+                    self.last_connection_failure = Some(Instant::now());
+                    self.buffer.clear();
+                    self.disconnected(&err);
+                }
+            }
+        }
+    }
+}
+
+impl RTDStateDataSource for StreamDataSource {
+    type Error = StreamDataSourceError;
+
+    fn read_packet(&mut self) -> Result<Option<Packet>, Self::Error> {
+        Err(StreamDataSourceError::Unsupported)
+    }
+
+    async fn read_packet_async(&mut self) -> Result<Option<Packet>, Self::Error> {
+        let frame = self.read_frame().await;
+        self.recorder.record(&frame);
+        let packet = match Packet::try_from(&frame[..]) {
+            Ok(packet) => packet,
+            // consoles send other kinds of packets that don't carry the
+            // sport's data, so they're skipped rather than reported
+            Err(PacketParseError::UnsupportedPacket { .. }) => return Ok(None),
+            Err(err) => return Err(StreamDataSourceError::Packet(err.to_string())),
+        };
+        self.sport_detector.observe(&frame);
+        self.raw_items.observe(&frame);
+        Ok(Some(packet))
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamDataSourceError {
+    Packet(String),
+    Unsupported,
+}
+
+impl Display for StreamDataSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamDataSourceError::Packet(err) => write!(f, "malformed packet: {}", err),
+            StreamDataSourceError::Unsupported => {
+                write!(f, "synchronous reads are unsupported on stream sources")
+            }
+        }
+    }
+}
+
+impl Error for StreamDataSourceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::backend::stream::frame::encode_frame;
+
+    /// A frame of a packet type that isn't the sport's data, with a header
+    /// that doesn't start with `004210`.
+    fn unsupported_frame() -> Vec<u8> {
+        let mut frame = vec![0x16, 0x01];
+        frame.extend_from_slice(b"0042900001");
+        frame.push(0x02);
+        frame.extend_from_slice(b"1");
+        frame.push(0x04);
+        frame.extend_from_slice(b"00");
+        frame.push(0x17);
+        frame
+    }
+
+    #[tokio::test]
+    async fn unsupported_packets_are_skipped() {
+        let feed = SimulatorFeed::new();
+        let mut data_source =
+            StreamDataSource::open(StreamInput::Simulator(feed.clone()), Default::default())
+                .unwrap();
+
+        feed.send(unsupported_frame());
+        feed.send(encode_frame(1, "1"));
+
+        assert!(data_source.read_packet_async().await.unwrap().is_none());
+        assert!(data_source.read_packet_async().await.unwrap().is_some());
+    }
+}
@@ -1,6 +1,7 @@
 mod configure;
 mod graph;
 mod header;
+mod simulator;
 mod stream_running;
 mod stream_start;
 mod utils;
@@ -16,8 +17,18 @@ use tokio::fs;
 use tokio::io::AsyncReadExt;
 use utils::{icon_button, rounded_button, rounded_pane, rounded_text_input_style};
 
+use crate::backend::capture::{default_capture_path, Recorder, CAPTURE_FILE_EXTENSION};
 use crate::backend::profile::Profile;
-use crate::backend::stream::{ActiveStream, WorkerEvent};
+use crate::backend::simulator::SportLayout;
+use crate::backend::sports::{
+    custom::{CustomSport, CUSTOM_SPORT_FILE_EXTENSION},
+    detection::detect_sport_on_serial,
+    raw::RAW_ITEM_KEY_PREFIX,
+    DynamicSportType,
+};
+use crate::backend::stream::{
+    serial::detect_serial_ports, source::StreamInput, ActiveStream, WorkerEvent,
+};
 use crate::{DAKTRONICS_SINGULAR_UI_PROFILE_FILE_EXTENSION, GITHUB_URL};
 
 #[derive(Debug)]
@@ -27,9 +38,13 @@ pub struct DaktronicsSingularUiApp {
     pub profile_dirty: bool,
     pub dark_mode: bool,
     pub sport_type_keys: Vec<String>,
+    /// Sports loaded from files this session
+    pub custom_sports: Vec<CustomSport>,
+    /// The sport's fields from the last stream, for previewing transformations
+    pub latest_source: serde_json::Map<String, serde_json::Value>,
     pub hide_header: bool,
     pub unattended: Option<usize>,
-    pub initial_tty_path: Option<String>,
+    pub initial_input: Option<StreamInput>,
     pub header: header::Header,
 }
 
@@ -45,9 +60,11 @@ impl Default for DaktronicsSingularUiApp {
             profile: Default::default(),
             dark_mode: use_dark_mode(),
             sport_type_keys: vec![],
+            custom_sports: vec![],
+            latest_source: Default::default(),
             hide_header: false,
             unattended: None,
-            initial_tty_path: None,
+            initial_input: None,
             header: header::Header::new(),
         }
     }
@@ -62,6 +79,7 @@ pub enum Message {
     ImportProfileFromPicker,
     ImportProfileFromPath(PathBuf),
     ImportProfileFinished(Profile),
+    CustomSportLoaded(CustomSport),
     ExportProfile,
     ExportProfileFinished,
     WelcomeNewProfile,
@@ -89,7 +107,8 @@ pub enum Message {
 pub enum Screen {
     Configure,
     SetUp(String),
-    StreamRunning(stream_running::StreamRunning, ActiveStream),
+    /// The stream is boxed since it's much bigger than the other screens
+    StreamRunning(stream_running::StreamRunning, Box<ActiveStream>),
     StreamStart(stream_start::StreamStart, Option<String>),
     #[default]
     Welcome,
@@ -97,7 +116,7 @@ pub enum Screen {
 
 impl Screen {
     pub fn stream_running(stream: ActiveStream) -> Self {
-        Self::StreamRunning(stream_running::StreamRunning::new(), stream)
+        Self::StreamRunning(stream_running::StreamRunning::new(), Box::new(stream))
     }
 }
 
@@ -223,7 +242,7 @@ impl DaktronicsSingularUiApp {
             Message::ImportProfileFinished(profile) => {
                 if let Some(sport_type) = profile.sport_type {
                     self.sport_type_keys = sport_type
-                        .all_serialized_keys()
+                        .all_serialized_keys(profile.sport_fields())
                         .expect("failed to get key list for sport");
                 } else {
                     self.sport_type_keys.clear();
@@ -268,6 +287,22 @@ impl DaktronicsSingularUiApp {
                 Task::none()
             }
             Message::EndStream => {
+                if let Screen::StreamRunning(_, ref stream) = self.screen {
+                    self.latest_source = stream
+                        .raw_fields()
+                        .iter()
+                        .map(|(key, field)| (key.clone(), field.value.clone()))
+                        .collect();
+                    if self.profile.sport_type == Some(DynamicSportType::Raw) {
+                        // the raw sport's item fields are only known once the
+                        // console sends them, so offer the ones it sent
+                        for key in stream.raw_fields().keys() {
+                            if !self.sport_type_keys.contains(key) {
+                                self.sport_type_keys.push(key.clone());
+                            }
+                        }
+                    }
+                }
                 // Drop the stream, killing the background threads implicitly
                 self.screen = Screen::StreamStart(stream_start::StreamStart::new(), None);
                 Task::none()
@@ -304,9 +339,8 @@ impl DaktronicsSingularUiApp {
             Message::UpdateStreamStatsResponse(events) => match self.screen {
                 Screen::StreamRunning(ref _stream_running, ref mut active_stream) => {
                     active_stream.update_from_events(events);
-                    if self.unattended.is_some()
-                        && self.initial_tty_path.is_some()
-                        && active_stream.errors().len() > self.unattended.unwrap()
+                    if self.initial_input.is_some()
+                        && active_stream.should_restart(self.unattended)
                     {
                         log::error!(
                             target: "frontend",
@@ -316,12 +350,13 @@ impl DaktronicsSingularUiApp {
                         );
                         match ActiveStream::new(
                             self.profile.clone(),
-                            self.initial_tty_path.as_ref().expect("no tty path").clone(),
+                            self.initial_input.as_ref().expect("no input").clone(),
+                            active_stream.recorder().clone(),
                         ) {
                             Ok(stream) => {
                                 self.screen = Screen::StreamRunning(
                                     stream_running::StreamRunning::new(),
-                                    stream,
+                                    Box::new(stream),
                                 );
                                 log::info!(target: "frontend", "Restarted stream successfully");
                             }
@@ -393,13 +428,97 @@ impl DaktronicsSingularUiApp {
             Message::ProfileConfigureMessage(message) => match self.profile.update(message) {
                 configure::Update::None => Task::none(),
                 configure::Update::RefreshSports(sport) => {
-                    self.sport_type_keys = sport
-                        .all_serialized_keys()
+                    let mut keys = sport
+                        .all_serialized_keys(self.profile.sport_fields())
                         .expect("failed to get key list for sport");
+                    if sport == DynamicSportType::Raw {
+                        // keep the items seen in earlier streams
+                        keys.extend(
+                            self.sport_type_keys
+                                .drain(..)
+                                .filter(|key| key.starts_with(RAW_ITEM_KEY_PREFIX)),
+                        );
+                    }
+                    self.sport_type_keys = keys;
                     self.profile.sport_type = Some(sport);
                     Task::none()
                 }
+                configure::Update::DetectSport => {
+                    let serial_settings = self.profile.serial;
+                    Task::future(async move {
+                        let result = detect_sport_on_serial(serial_settings).await.and_then(
+                            |(port_name, detection)| {
+                                detection
+                                    .best()
+                                    .map(|best| (port_name, detection.packets, best))
+                                    .ok_or_else(|| {
+                                        "the packets from the console don't fit any known sport"
+                                            .to_owned()
+                                    })
+                            },
+                        );
+                        match result {
+                            Ok((port_name, packets, best)) => {
+                                match rfd::AsyncMessageDialog::new()
+                                    .set_level(rfd::MessageLevel::Info)
+                                    .set_title("Detected sport")
+                                    .set_description(format!(
+                                        "From {} packets on {}, the console looks like it's set to {}. Use {} for this profile?",
+                                        packets, port_name, best, best
+                                    ))
+                                    .set_buttons(rfd::MessageButtons::OkCancel)
+                                    .show()
+                                    .await
+                                {
+                                    rfd::MessageDialogResult::Ok => Message::ProfileConfigureMessage(
+                                        ConfigureMessage::SportTypeUpdated(best),
+                                    ),
+                                    _ => Message::NoOp,
+                                }
+                            }
+                            Err(err) => {
+                                rfd::AsyncMessageDialog::new()
+                                    .set_level(rfd::MessageLevel::Error)
+                                    .set_title("Failed to detect sport")
+                                    .set_description(err)
+                                    .show()
+                                    .await;
+                                Message::NoOp
+                            }
+                        }
+                    })
+                }
+                configure::Update::LoadCustomSport => Task::future(async {
+                    let Some(file) = rfd::AsyncFileDialog::new()
+                        .set_title("Open sport file")
+                        .add_filter("Sport definition", &[CUSTOM_SPORT_FILE_EXTENSION])
+                        .pick_file()
+                        .await
+                    else {
+                        return Message::NoOp;
+                    };
+                    match CustomSport::load(file.path()).await {
+                        Ok(sport) => Message::CustomSportLoaded(sport),
+                        Err(err) => {
+                            rfd::AsyncMessageDialog::new()
+                                .set_level(rfd::MessageLevel::Error)
+                                .set_title("Failed to load sport file")
+                                .set_description(err)
+                                .show()
+                                .await;
+                            Message::NoOp
+                        }
+                    }
+                }),
             },
+            Message::CustomSportLoaded(sport) => {
+                // reloading a file replaces the sport loaded from it before
+                self.custom_sports.retain(|loaded| loaded.name != sport.name);
+                self.custom_sports.push(sport.clone());
+                Task::done(Message::ProfileConfigureMessage(
+                    ConfigureMessage::CustomSportUpdated(sport),
+                ))
+            }
             Message::HeaderMessage(message) => match self.header.update(message) {
                 // TODO: many of these messages can just be moved here.
                 header::Update::None => Task::none(),
@@ -420,6 +539,27 @@ impl DaktronicsSingularUiApp {
                             stream.clear_errors();
                             Task::none()
                         }
+                        stream_running::Update::CopyRawFields => {
+                            iced::clipboard::write(stream.raw_fields_snapshot())
+                        }
+                        stream_running::Update::StepReplay => {
+                            if let Some(replay_control) = stream.replay_control() {
+                                replay_control.step();
+                            }
+                            Task::none()
+                        }
+                        stream_running::Update::ToggleRecording => {
+                            if stream.recorder().path().is_some() {
+                                stream.recorder().stop();
+                            } else if let Err(err) =
+                                stream.start_recording(&default_capture_path(&self.profile.name))
+                            {
+                                stream.update_from_events(vec![WorkerEvent::ErrorEvent(
+                                    format!("failed to start recording: {err}").into(),
+                                )]);
+                            }
+                            Task::none()
+                        }
                     }
                 }
                 _ => Task::none(),
@@ -428,18 +568,85 @@ impl DaktronicsSingularUiApp {
                 Screen::StreamStart(ref mut screen_start, ref mut error) => {
                     match screen_start.update(message) {
                         stream_start::Update::None => Task::none(),
-                        stream_start::Update::StartStream { port } => {
-                            if self.profile.sport_type.is_some() {
-                                match ActiveStream::new(self.profile.to_owned(), port) {
+                        stream_start::Update::DetectSerialPort => {
+                            Task::perform(detect_serial_ports(self.profile.serial), |scores| {
+                                Message::StreamStartMessage(
+                                    stream_start::StreamStartMessage::SerialPortsDetected(scores),
+                                )
+                            })
+                        }
+                        stream_start::Update::SerialSettingsUpdated(new_settings) => {
+                            self.profile.serial = new_settings;
+                            self.profile_dirty = true;
+                            Task::none()
+                        }
+                        stream_start::Update::PickCapture => Task::future(async {
+                            if let Some(path) = rfd::AsyncFileDialog::new()
+                                .set_title("Open capture")
+                                .add_filter(
+                                    "Daktronics Singular UI Capture",
+                                    &[CAPTURE_FILE_EXTENSION],
+                                )
+                                .pick_file()
+                                .await
+                            {
+                                Message::StreamStartMessage(
+                                    stream_start::StreamStartMessage::CapturePicked(
+                                        path.path().to_path_buf(),
+                                    ),
+                                )
+                            } else {
+                                Message::NoOp
+                            }
+                        }),
+                        stream_start::Update::StartStream { input } => {
+                            if let Some(sport_type) = self.profile.sport_type {
+                                let simulator_feed = match input {
+                                    StreamInput::Simulator(ref feed) => Some(feed.clone()),
+                                    _ => None,
+                                };
+                                match ActiveStream::new(
+                                    self.profile.to_owned(),
+                                    input,
+                                    Recorder::default(),
+                                ) {
                                     Ok(stream) => {
                                         self.screen = Screen::StreamRunning(
-                                            stream_running::StreamRunning::new(),
-                                            stream,
+                                            match simulator_feed {
+                                                Some(ref feed) => {
+                                                    stream_running::StreamRunning::with_simulator(
+                                                        feed.clone(),
+                                                    )
+                                                }
+                                                None => stream_running::StreamRunning::new(),
+                                            },
+                                            Box::new(stream),
                                         );
                                     }
                                     Err(err) => *error = Some(err.to_string()),
                                 }
-                                Task::done(Message::UpdateStreamStats)
+                                Task::batch([
+                                    Task::done(Message::UpdateStreamStats),
+                                    if simulator_feed.is_some() {
+                                        Task::perform(
+                                            SportLayout::probe(
+                                                sport_type,
+                                                self.profile.sport_fields().to_vec(),
+                                            ),
+                                            |layout| {
+                                                Message::StreamRunningMessage(
+                                                    stream_running::StreamRunningMessage::Simulator(
+                                                        simulator::SimulatorMessage::LayoutProbed(
+                                                            layout,
+                                                        ),
+                                                    ),
+                                                )
+                                            },
+                                        )
+                                    } else {
+                                        Task::none()
+                                    },
+                                ])
                             } else {
                                 *error = Some(
                                     "You must set a sport type before beginning the stream."
@@ -456,7 +663,13 @@ impl DaktronicsSingularUiApp {
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
-        iced::event::listen_with(|event, status, _id| {
+        let screen_subscription = match self.screen {
+            Screen::StreamRunning(ref stream_running, _) => stream_running
+                .subscription()
+                .map(Message::StreamRunningMessage),
+            _ => Subscription::none(),
+        };
+        let event_subscription = iced::event::listen_with(|event, status, _id| {
             if matches!(status, iced::event::Status::Ignored) {
                 match event {
                     iced::Event::Window(window_event) => match window_event {
@@ -471,7 +684,8 @@ impl DaktronicsSingularUiApp {
             } else {
                 None
             }
-        })
+        });
+        Subscription::batch([event_subscription, screen_subscription])
     }
 
     pub fn view(&self) -> Element<Message> {
@@ -543,7 +757,7 @@ impl DaktronicsSingularUiApp {
                     .map(Message::HeaderMessage)
                 },
                 match &self.screen {
-                    Screen::Configure => self.profile.view(&self.sport_type_keys).map(Message::ProfileConfigureMessage)
+                    Screen::Configure => self.profile.view(&self.sport_type_keys, &self.custom_sports, &self.latest_source).map(Message::ProfileConfigureMessage)
                     .into(),
                     Screen::SetUp(public_token) => container(
                         scrollable(
@@ -629,7 +843,7 @@ impl DaktronicsSingularUiApp {
                         stream_running.view(active_stream).map(Message::StreamRunningMessage)
                     }
                     Screen::StreamStart(stream_start, error) => {
-                        stream_start.view(error.as_deref(), self.profile_dirty).map(Message::StreamStartMessage)
+                        stream_start.view(error.as_deref(), self.profile_dirty, self.profile.serial).map(Message::StreamStartMessage)
                     }
                     Screen::Welcome => unreachable!(),
                 },
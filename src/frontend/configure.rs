@@ -1,3 +1,5 @@
+use std::borrow::Borrow;
+
 use iced::{
     border,
     widget::{
@@ -7,10 +9,26 @@ use iced::{
     Border, Element, Length, Renderer, Theme,
 };
 
+use serde_json::{Map, Value};
+
 use crate::backend::{
-    mapping::transformation::Transformation,
+    mapping::{
+        change::ItemOutput,
+        clock::ClockFormat,
+        lookup::{LookupFallback, LookupTable},
+        path::DestinationPath,
+        pattern::{RegexExtract, RegexReplace},
+        repeat::{numbered_families, RepeatMapping, NUMBER_PLACEHOLDER},
+        text::{PadSide, Padding, TextCase, Truncation},
+        transformation::{Transformation, TransformationError},
+        MappingItem, MissingDataPolicy,
+    },
     profile::{Profile, ProfileCompositionMapping},
-    sports::DynamicSportType,
+    sports::{
+        custom::{CustomSport, SportChoice},
+        raw::RawFieldDefinition,
+        DynamicSportType,
+    },
 };
 
 use super::utils::{
@@ -23,8 +41,21 @@ pub enum ProfileCompositionMessage {
     ItemRemoved(usize),
     ItemEnabledUpdated(usize, bool),
     ItemSourceFieldUpdated(usize, String),
-    ItemTransformationUpdated(usize, Transformation),
+    ItemTransformationAdded(usize),
+    ItemTransformationRemoved(usize, usize),
+    ItemTransformationMovedUp(usize, usize),
+    ItemTransformationUpdated(usize, usize, Transformation),
     ItemDestinationFieldUpdated(usize, String),
+    ItemMissingDataUpdated(usize, MissingDataPolicy),
+    ItemOutputUpdated(usize, ItemOutput),
+    RepeatAdded,
+    RepeatRemoved(usize),
+    RepeatEnabledUpdated(usize, bool),
+    RepeatDestinationFieldUpdated(usize, String),
+    RepeatSortByUpdated(usize, String),
+    RepeatSortDescendingUpdated(usize, bool),
+    /// A message about one of the repeat's items
+    RepeatItem(usize, Box<ProfileCompositionMessage>),
     SubcompNameUpdated(String),
     CheckboxNameUpdated(String),
 }
@@ -33,6 +64,7 @@ trait ProfileCompositionMappingExt {
     fn view<'a>(
         &'a self,
         sport_type_keys: &'a Vec<String>,
+        latest_source: &'a Map<String, Value>,
     ) -> iced::Element<'a, ProfileCompositionMessage>;
     fn update(&mut self, message: ProfileCompositionMessage);
 }
@@ -41,6 +73,7 @@ impl ProfileCompositionMappingExt for ProfileCompositionMapping {
     fn view<'a>(
         &'a self,
         sport_type_keys: &'a Vec<String>,
+        latest_source: &'a Map<String, Value>,
     ) -> iced::Element<'a, ProfileCompositionMessage> {
         column([
             row([
@@ -81,64 +114,38 @@ impl ProfileCompositionMappingExt for ProfileCompositionMapping {
             .spacing(8)
             .into(),
             column(self.mapping.items.iter().enumerate().map(|(i, item)| {
+                item_view(
+                    i,
+                    item,
+                    sport_type_keys.as_slice(),
+                    latest_source.get(&item.source_field),
+                    latest_source,
+                )
+            }))
+            .spacing(8)
+            .into(),
+            column(
+                self.mapping
+                    .repeats
+                    .iter()
+                    .enumerate()
+                    .map(|(r, repeat)| repeat_view(r, repeat, sport_type_keys, latest_source)),
+            )
+            .spacing(8)
+            .into(),
+            container(
                 row([
-                    Element::from(icon_button(
-                        include_bytes!("../../assets/icon_delete.svg"),
-                        "Remove mapping",
-                        Some(()),
-                        super::utils::RoundedButtonVariant::Danger,
-                    ))
-                    .map(move |_| ProfileCompositionMessage::ItemRemoved(i)),
-                    Element::from(icon_button(
-                        if item.enabled {
-                            include_bytes!("../../assets/icon_check_box.svg")
-                        } else {
-                            include_bytes!("../../assets/icon_check_box_outline_blank.svg")
-                        },
-                        if item.enabled {
-                            "Disable mapping"
-                        } else {
-                            "Enable mapping"
-                        },
-                        Some(()),
+                    rounded_button("New mapping", super::utils::RoundedButtonVariant::Secondary)
+                        .on_press(ProfileCompositionMessage::ItemAdded)
+                        .into(),
+                    rounded_button(
+                        "New repeated mapping",
                         super::utils::RoundedButtonVariant::Secondary,
-                    ))
-                    .map(move |_| ProfileCompositionMessage::ItemEnabledUpdated(i, !item.enabled))
-                    .into(),
-                    pick_list(
-                        sport_type_keys.as_ref(),
-                        Some(&item.source_field),
-                        move |new| ProfileCompositionMessage::ItemSourceFieldUpdated(i, new),
                     )
-                    .width(Length::Fill)
-                    .padding(8)
-                    .style(rounded_pick_list_style)
-                    .into(),
-                    pick_list(Transformation::ALL, Some(item.transformation), move |new| {
-                        ProfileCompositionMessage::ItemTransformationUpdated(i, new)
-                    })
-                    .width(Length::Fill)
-                    .padding(8)
-                    .style(rounded_pick_list_style)
+                    .on_press(ProfileCompositionMessage::RepeatAdded)
                     .into(),
-                    text_input("Destination field", &item.destination_field)
-                        .width(Length::Fill)
-                        .padding(8)
-                        .on_input(move |new| {
-                            ProfileCompositionMessage::ItemDestinationFieldUpdated(i, new)
-                        })
-                        .style(rounded_text_input_style)
-                        .into(),
                 ])
-                .spacing(8)
-                .align_y(iced::Alignment::Center)
-                .into()
-            }))
-            .spacing(8)
-            .into(),
-            container(
-                rounded_button("New mapping", super::utils::RoundedButtonVariant::Secondary)
-                    .on_press(ProfileCompositionMessage::ItemAdded),
+                .spacing(8),
             )
             .center_x(Length::Fill)
             .into(),
@@ -149,23 +156,26 @@ impl ProfileCompositionMappingExt for ProfileCompositionMapping {
 
     fn update(&mut self, message: ProfileCompositionMessage) {
         match message {
-            ProfileCompositionMessage::ItemAdded => {
-                self.mapping.items.push(Default::default());
+            ProfileCompositionMessage::RepeatAdded => {
+                self.mapping.repeats.push(Default::default());
+            }
+            ProfileCompositionMessage::RepeatRemoved(r) => {
+                self.mapping.repeats.remove(r);
             }
-            ProfileCompositionMessage::ItemRemoved(i) => {
-                self.mapping.items.remove(i);
+            ProfileCompositionMessage::RepeatEnabledUpdated(r, new) => {
+                self.mapping.repeats[r].enabled = new;
             }
-            ProfileCompositionMessage::ItemEnabledUpdated(i, new) => {
-                self.mapping.items[i].enabled = new;
+            ProfileCompositionMessage::RepeatDestinationFieldUpdated(r, new) => {
+                self.mapping.repeats[r].destination_field = new;
             }
-            ProfileCompositionMessage::ItemSourceFieldUpdated(i, new) => {
-                self.mapping.items[i].source_field = new;
+            ProfileCompositionMessage::RepeatSortByUpdated(r, new) => {
+                self.mapping.repeats[r].sort_by = new;
             }
-            ProfileCompositionMessage::ItemTransformationUpdated(i, new) => {
-                self.mapping.items[i].transformation = new;
+            ProfileCompositionMessage::RepeatSortDescendingUpdated(r, new) => {
+                self.mapping.repeats[r].sort_descending = new;
             }
-            ProfileCompositionMessage::ItemDestinationFieldUpdated(i, new) => {
-                self.mapping.items[i].destination_field = new;
+            ProfileCompositionMessage::RepeatItem(r, message) => {
+                update_items(&mut self.mapping.repeats[r].items, *message);
             }
             ProfileCompositionMessage::SubcompNameUpdated(new) => {
                 self.subcomp_name = new;
@@ -173,27 +183,84 @@ impl ProfileCompositionMappingExt for ProfileCompositionMapping {
             ProfileCompositionMessage::CheckboxNameUpdated(new) => {
                 self.enabled_checkbox_name = if new.is_empty() { None } else { Some(new) }
             }
+            message => update_items(&mut self.mapping.items, message),
         }
     }
 }
 
+/// Applies a message about the mapping items to `items`, which are either the
+/// mapping's or a repeat's.
+fn update_items(items: &mut Vec<MappingItem>, message: ProfileCompositionMessage) {
+    match message {
+        ProfileCompositionMessage::ItemAdded => {
+            items.push(Default::default());
+        }
+        ProfileCompositionMessage::ItemRemoved(i) => {
+            items.remove(i);
+        }
+        ProfileCompositionMessage::ItemEnabledUpdated(i, new) => {
+            items[i].enabled = new;
+        }
+        ProfileCompositionMessage::ItemSourceFieldUpdated(i, new) => {
+            items[i].source_field = new;
+        }
+        ProfileCompositionMessage::ItemTransformationAdded(i) => {
+            items[i].transformations.push(Default::default());
+        }
+        ProfileCompositionMessage::ItemTransformationRemoved(i, j) => {
+            items[i].transformations.remove(j);
+        }
+        ProfileCompositionMessage::ItemTransformationMovedUp(i, j) => {
+            items[i].transformations.swap(j - 1, j);
+        }
+        ProfileCompositionMessage::ItemTransformationUpdated(i, j, new) => {
+            items[i].transformations[j] = new;
+        }
+        ProfileCompositionMessage::ItemDestinationFieldUpdated(i, new) => {
+            items[i].destination_field = new;
+        }
+        ProfileCompositionMessage::ItemMissingDataUpdated(i, new) => {
+            items[i].missing_data = new;
+        }
+        ProfileCompositionMessage::ItemOutputUpdated(i, new) => {
+            items[i].output = new;
+        }
+        // the rest aren't about the items
+        _ => {}
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Update {
     None,
     RefreshSports(DynamicSportType),
+    DetectSport,
+    LoadCustomSport,
 }
 
 pub trait ProfileConfigureExt {
     fn update(&mut self, message: ConfigureMessage) -> Update;
-    fn view<'a>(&'a self, sport_type_keys: &'a Vec<String>) -> iced::Element<'a, ConfigureMessage>;
+    fn view<'a>(
+        &'a self,
+        sport_type_keys: &'a Vec<String>,
+        custom_sports: &'a [CustomSport],
+        latest_source: &'a Map<String, Value>,
+    ) -> iced::Element<'a, ConfigureMessage>;
 }
 
 #[derive(Clone, Debug)]
 pub enum ConfigureMessage {
     DataStreamUrlUpdated(String),
     SportTypeUpdated(DynamicSportType),
+    CustomSportUpdated(CustomSport),
+    DetectSport,
+    LoadCustomSport,
     MultipleRequestsUpdated(bool),
     ExcludeIncompleteDataUpdated(bool),
+    StaleDataTimeoutUpdated(String),
+    RawFieldAdded,
+    RawFieldRemoved(usize),
+    RawFieldUpdated(usize, RawFieldDefinition),
     MappingMessage(usize, ProfileCompositionMessage),
     AddSubcompMapping,
 }
@@ -213,6 +280,27 @@ impl ProfileConfigureExt for Profile {
                 self.exclude_incomplete_data = new;
                 Update::None
             }
+            ConfigureMessage::StaleDataTimeoutUpdated(new) => {
+                // blank disables the watchdog, anything else non-numeric is ignored
+                if new.is_empty() {
+                    self.stale_data_timeout_ms = 0;
+                } else if let Ok(new) = new.parse() {
+                    self.stale_data_timeout_ms = new;
+                }
+                Update::None
+            }
+            ConfigureMessage::RawFieldAdded => {
+                self.raw_fields.push(Default::default());
+                Update::RefreshSports(DynamicSportType::Raw)
+            }
+            ConfigureMessage::RawFieldRemoved(i) => {
+                self.raw_fields.remove(i);
+                Update::RefreshSports(DynamicSportType::Raw)
+            }
+            ConfigureMessage::RawFieldUpdated(i, new) => {
+                self.raw_fields[i] = new;
+                Update::RefreshSports(DynamicSportType::Raw)
+            }
             ConfigureMessage::MappingMessage(i, msg) => {
                 self.mappings[i].update(msg);
                 Update::None
@@ -225,12 +313,21 @@ impl ProfileConfigureExt for Profile {
                 self.sport_type = Some(new);
                 Update::RefreshSports(new)
             }
+            ConfigureMessage::CustomSportUpdated(new) => {
+                self.sport_type = Some(DynamicSportType::Custom);
+                self.custom_sport = Some(new);
+                Update::RefreshSports(DynamicSportType::Custom)
+            }
+            ConfigureMessage::DetectSport => Update::DetectSport,
+            ConfigureMessage::LoadCustomSport => Update::LoadCustomSport,
         }
     }
 
     fn view<'a>(
         &'a self,
         sport_type_keys: &'a Vec<String>,
+        custom_sports: &'a [CustomSport],
+        latest_source: &'a Map<String, Value>,
     ) -> Element<'a, ConfigureMessage, Theme, Renderer> {
         scrollable(
             column([
@@ -259,14 +356,38 @@ impl ProfileConfigureExt for Profile {
                                 color: Some(theme.palette().text.scale_alpha(0.6))
                             })
                             .into(),
-                        pick_list(
-                            DynamicSportType::ALL,
-                            self.sport_type.as_ref(),
-                            ConfigureMessage::SportTypeUpdated,
-                        )
-                        .width(Length::Fill)
-                        .padding(8)
-                        .style(rounded_pick_list_style)
+                        row([
+                            pick_list(
+                                sport_choices(custom_sports, self.custom_sport.as_ref()),
+                                self.sport_type.map(|sport_type| match (sport_type, &self.custom_sport) {
+                                    (DynamicSportType::Custom, Some(custom_sport)) => {
+                                        SportChoice::Custom(custom_sport.clone())
+                                    }
+                                    (sport_type, _) => SportChoice::BuiltIn(sport_type),
+                                }),
+                                |choice| match choice {
+                                    SportChoice::BuiltIn(sport_type) => {
+                                        ConfigureMessage::SportTypeUpdated(sport_type)
+                                    }
+                                    SportChoice::Custom(custom_sport) => {
+                                        ConfigureMessage::CustomSportUpdated(custom_sport)
+                                    }
+                                },
+                            )
+                            .width(Length::Fill)
+                            .padding(8)
+                            .style(rounded_pick_list_style)
+                            .into(),
+                            rounded_button("Load sport file", super::utils::RoundedButtonVariant::Secondary)
+                                .on_press(ConfigureMessage::LoadCustomSport)
+                                .padding(8)
+                                .into(),
+                            rounded_button("Detect from console", super::utils::RoundedButtonVariant::Secondary)
+                                .on_press(ConfigureMessage::DetectSport)
+                                .padding(8)
+                                .into(),
+                        ])
+                        .spacing(4)
                         .into(),
                     ])
                     .spacing(4)
@@ -280,6 +401,31 @@ impl ProfileConfigureExt for Profile {
                 checkbox("Exclude incomplete data from payload instead of erroring", self.exclude_incomplete_data)
                     .on_toggle(ConfigureMessage::ExcludeIncompleteDataUpdated)
                     .into(),
+                column([
+                    text("Mark data stale after no updates for (ms, blank to disable)")
+                        .style(|theme: &Theme| text::Style {
+                            color: Some(theme.palette().text.scale_alpha(0.6))
+                        })
+                        .into(),
+                    text_input(
+                        "3000",
+                        &match self.stale_data_timeout_ms {
+                            0 => String::new(),
+                            timeout => timeout.to_string(),
+                        },
+                    )
+                    .width(Length::Fixed(200.0))
+                    .padding(8)
+                    .on_input(ConfigureMessage::StaleDataTimeoutUpdated)
+                    .style(rounded_text_input_style)
+                    .into(),
+                ])
+                .spacing(4)
+                .into(),
+                match self.sport_type {
+                    Some(DynamicSportType::Raw) => raw_fields_view(&self.raw_fields),
+                    _ => column([]).into(),
+                },
                 horizontal_rule(2.0).into(),
                 column(
                     self.mappings
@@ -287,7 +433,7 @@ impl ProfileConfigureExt for Profile {
                         .enumerate()
                         .map(|(i, mapping)|
                             container(mapping
-                                .view(&sport_type_keys)
+                                .view(&sport_type_keys, latest_source)
                                 .map(move |msg|ConfigureMessage::MappingMessage(i, msg))
                             )
                             .style(|theme| container::Style {
@@ -362,3 +508,955 @@ impl ProfileConfigureExt for Profile {
         .into()
     }
 }
+
+/// The built-in sports followed by the loaded custom sports, including the
+/// profile's own if it wasn't loaded from a file this session.
+fn sport_choices(
+    custom_sports: &[CustomSport],
+    profile_custom_sport: Option<&CustomSport>,
+) -> Vec<SportChoice> {
+    let mut choices = DynamicSportType::ALL
+        .into_iter()
+        .map(SportChoice::BuiltIn)
+        .chain(custom_sports.iter().cloned().map(SportChoice::Custom))
+        .collect::<Vec<_>>();
+    if let Some(custom_sport) = profile_custom_sport.filter(|sport| !custom_sports.contains(sport))
+    {
+        choices.push(SportChoice::Custom(custom_sport.clone()));
+    }
+    choices
+}
+
+/// A mapping item's row, with its transformations and output under it.
+/// `source_value` is the item's source field's data from the last stream.
+fn item_view<'a>(
+    i: usize,
+    item: &'a MappingItem,
+    source_fields: impl Borrow<[String]> + 'a,
+    source_value: Option<&'a Value>,
+    latest_source: &'a Map<String, Value>,
+) -> Element<'a, ProfileCompositionMessage> {
+    let item_row = row([
+        Element::from(icon_button(
+            include_bytes!("../../assets/icon_delete.svg"),
+            "Remove mapping",
+            Some(()),
+            super::utils::RoundedButtonVariant::Danger,
+        ))
+        .map(move |_| ProfileCompositionMessage::ItemRemoved(i)),
+        Element::from(icon_button(
+            if item.enabled {
+                include_bytes!("../../assets/icon_check_box.svg")
+            } else {
+                include_bytes!("../../assets/icon_check_box_outline_blank.svg")
+            },
+            if item.enabled {
+                "Disable mapping"
+            } else {
+                "Enable mapping"
+            },
+            Some(()),
+            super::utils::RoundedButtonVariant::Secondary,
+        ))
+        .map(move |_| ProfileCompositionMessage::ItemEnabledUpdated(i, !item.enabled))
+        .into(),
+        pick_list(source_fields, Some(&item.source_field), move |new| {
+            ProfileCompositionMessage::ItemSourceFieldUpdated(i, new)
+        })
+        .width(Length::Fill)
+        .padding(8)
+        .style(rounded_pick_list_style)
+        .into(),
+        text_input("Destination field or path", &item.destination_field)
+            .width(Length::Fill)
+            .padding(8)
+            .on_input(move |new| ProfileCompositionMessage::ItemDestinationFieldUpdated(i, new))
+            .style(rounded_text_input_style)
+            .into(),
+        pick_list(
+            MissingDataPolicy::ALL,
+            Some(&item.missing_data),
+            move |new| ProfileCompositionMessage::ItemMissingDataUpdated(i, new),
+        )
+        .width(Length::Fill)
+        .padding(8)
+        .style(rounded_pick_list_style)
+        .into(),
+    ])
+    .push_maybe(match &item.missing_data {
+        MissingDataPolicy::Fallback(fallback) => Some(
+            text_input("Fallback text", fallback)
+                .width(Length::Fill)
+                .padding(8)
+                .on_input(move |new| {
+                    ProfileCompositionMessage::ItemMissingDataUpdated(
+                        i,
+                        MissingDataPolicy::Fallback(new),
+                    )
+                })
+                .style(rounded_text_input_style),
+        ),
+        _ => None,
+    })
+    .spacing(8)
+    .align_y(iced::Alignment::Center);
+    column([item_row.into()])
+        .push_maybe(
+            DestinationPath::parse(&item.destination_field)
+                .err()
+                .map(|err| text(err.to_string()).style(text::danger)),
+        )
+        .push(transformations_view(
+            i,
+            &item.transformations,
+            source_value,
+            latest_source,
+        ))
+        .push(output_view(i, item.output))
+        .spacing(8)
+        .into()
+}
+
+/// A repeat's settings and items, which pick their source fields from the
+/// sport's numbered families.
+fn repeat_view<'a>(
+    r: usize,
+    repeat: &'a RepeatMapping,
+    sport_type_keys: &'a [String],
+    latest_source: &'a Map<String, Value>,
+) -> Element<'a, ProfileCompositionMessage> {
+    let source_fields: Vec<String> = numbered_families(sport_type_keys)
+        .into_iter()
+        .chain(sport_type_keys.iter().cloned())
+        .collect();
+    let numbers = repeat.numbers(sport_type_keys);
+    // previews show the first number's data
+    let latest_digits = repeat
+        .numbers(latest_source.keys())
+        .first()
+        .map(|(_, digits)| *digits);
+    let settings_row = row([
+        Element::from(icon_button(
+            include_bytes!("../../assets/icon_delete.svg"),
+            "Remove repeated mapping",
+            Some(()),
+            super::utils::RoundedButtonVariant::Danger,
+        ))
+        .map(move |_| ProfileCompositionMessage::RepeatRemoved(r)),
+        Element::from(icon_button(
+            if repeat.enabled {
+                include_bytes!("../../assets/icon_check_box.svg")
+            } else {
+                include_bytes!("../../assets/icon_check_box_outline_blank.svg")
+            },
+            if repeat.enabled {
+                "Disable repeated mapping"
+            } else {
+                "Enable repeated mapping"
+            },
+            Some(()),
+            super::utils::RoundedButtonVariant::Secondary,
+        ))
+        .map(move |_| ProfileCompositionMessage::RepeatEnabledUpdated(r, !repeat.enabled)),
+        text_input(
+            "Destination field or path of the array",
+            &repeat.destination_field,
+        )
+        .width(Length::Fill)
+        .padding(8)
+        .on_input(move |new| ProfileCompositionMessage::RepeatDestinationFieldUpdated(r, new))
+        .style(rounded_text_input_style)
+        .into(),
+        text_input(
+            "Sort by field or path (blank for number order)",
+            &repeat.sort_by,
+        )
+        .width(Length::Fill)
+        .padding(8)
+        .on_input(move |new| ProfileCompositionMessage::RepeatSortByUpdated(r, new))
+        .style(rounded_text_input_style)
+        .into(),
+    ])
+    .push_maybe((!repeat.sort_by.is_empty()).then(|| {
+        checkbox("Descending", repeat.sort_descending)
+            .on_toggle(move |new| ProfileCompositionMessage::RepeatSortDescendingUpdated(r, new))
+    }))
+    .spacing(8)
+    .align_y(iced::Alignment::Center);
+    let numbers_hint = match (numbers.first(), numbers.last()) {
+        (Some((first, _)), Some((last, _))) => format!(
+            "An object for each of {} numbers, {} to {}",
+            numbers.len(),
+            first,
+            last
+        ),
+        _ => format!(
+            "No source fields match; pick a numbered family like lane_{NUMBER_PLACEHOLDER}_place"
+        ),
+    };
+    container(
+        column([
+            settings_row.into(),
+            text(numbers_hint)
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.palette().text.scale_alpha(0.6)),
+                })
+                .into(),
+        ])
+        .push_maybe(
+            DestinationPath::parse(&repeat.destination_field)
+                .err()
+                .map(|err| text(err.to_string()).style(text::danger)),
+        )
+        .push_maybe(
+            (!repeat.sort_by.is_empty())
+                .then(|| DestinationPath::parse(&repeat.sort_by).err())
+                .flatten()
+                .map(|err| text(format!("Sort by: {}", err)).style(text::danger)),
+        )
+        .extend(repeat.items.iter().enumerate().map(|(i, item)| {
+            let source_value = latest_digits.and_then(|digits| {
+                latest_source.get(&item.source_field.replacen(NUMBER_PLACEHOLDER, digits, 1))
+            });
+            item_view(i, item, source_fields.clone(), source_value, latest_source)
+                .map(move |message| ProfileCompositionMessage::RepeatItem(r, Box::new(message)))
+        }))
+        .push(
+            container(
+                rounded_button("New field", super::utils::RoundedButtonVariant::Secondary)
+                    .on_press(ProfileCompositionMessage::RepeatItem(
+                        r,
+                        Box::new(ProfileCompositionMessage::ItemAdded),
+                    )),
+            )
+            .center_x(Length::Fill),
+        )
+        .spacing(8),
+    )
+    .style(|theme: &Theme| container::Style {
+        border: Border {
+            color: theme.extended_palette().background.strong.color,
+            width: 1.0,
+            radius: 12.into(),
+        },
+        ..Default::default()
+    })
+    .padding(8)
+    .into()
+}
+
+/// What an item outputs, with the options for outputting its changes.
+fn output_view<'a>(i: usize, output: ItemOutput) -> Element<'a, ProfileCompositionMessage> {
+    let updated = move |new| ProfileCompositionMessage::ItemOutputUpdated(i, new);
+    let options: Vec<Element<'a, ProfileCompositionMessage>> = match output {
+        ItemOutput::Value => vec![],
+        ItemOutput::Pulse {
+            only_on_increase,
+            hold_ms,
+        } => vec![
+            text_input(
+                "Hold true for (milliseconds)",
+                &number_input_value(hold_ms as usize),
+            )
+            .width(Length::Fill)
+            .padding(8)
+            .on_input(move |hold| {
+                updated(ItemOutput::Pulse {
+                    only_on_increase,
+                    hold_ms: parse_number_input(&hold, hold_ms as usize) as u64,
+                })
+            })
+            .style(rounded_text_input_style)
+            .into(),
+            checkbox("Only when it increases", only_on_increase)
+                .on_toggle(move |only_on_increase| {
+                    updated(ItemOutput::Pulse {
+                        only_on_increase,
+                        hold_ms,
+                    })
+                })
+                .width(Length::Fill)
+                .into(),
+        ],
+        ItemOutput::ChangeCount { only_on_increase } => {
+            vec![checkbox("Only count increases", only_on_increase)
+                .on_toggle(move |only_on_increase| {
+                    updated(ItemOutput::ChangeCount { only_on_increase })
+                })
+                .width(Length::Fill)
+                .into()]
+        }
+    };
+    row(std::iter::once(
+        pick_list(ItemOutput::ALL, Some(output), updated)
+            .width(Length::Fill)
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+    )
+    .chain(options))
+    .spacing(8)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+/// Shows a number, or nothing for 0 so the field can be cleared while typing.
+fn number_input_value(value: usize) -> String {
+    match value {
+        0 => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Parses what was typed into a number field, keeping `current` if it isn't a
+/// number.
+fn parse_number_input(input: &str, current: usize) -> usize {
+    if input.is_empty() {
+        0
+    } else {
+        input.parse().unwrap_or(current)
+    }
+}
+
+/// The named fields of the raw sport, each cut out of an item's text.
+fn raw_fields_view(raw_fields: &[RawFieldDefinition]) -> Element<'_, ConfigureMessage> {
+    column([
+        text("Raw fields (every item is also available as item_<item number>)")
+            .style(|theme: &Theme| text::Style {
+                color: Some(theme.palette().text.scale_alpha(0.6)),
+            })
+            .into(),
+        column(raw_fields.iter().enumerate().map(|(i, field)| {
+            row([
+                Element::from(icon_button(
+                    include_bytes!("../../assets/icon_delete.svg"),
+                    "Remove field",
+                    Some(()),
+                    super::utils::RoundedButtonVariant::Danger,
+                ))
+                .map(move |_| ConfigureMessage::RawFieldRemoved(i)),
+                text_input("Field name", &field.name)
+                    .width(Length::FillPortion(2))
+                    .padding(8)
+                    .on_input(move |name| {
+                        ConfigureMessage::RawFieldUpdated(
+                            i,
+                            RawFieldDefinition {
+                                name,
+                                ..field.clone()
+                            },
+                        )
+                    })
+                    .style(rounded_text_input_style)
+                    .into(),
+                text_input("Item number (0042101)", &field.item_number)
+                    .width(Length::FillPortion(2))
+                    .padding(8)
+                    .on_input(move |item_number| {
+                        ConfigureMessage::RawFieldUpdated(
+                            i,
+                            RawFieldDefinition {
+                                item_number,
+                                ..field.clone()
+                            },
+                        )
+                    })
+                    .style(rounded_text_input_style)
+                    .into(),
+                text_input("Offset", &number_input_value(field.offset))
+                    .width(Length::FillPortion(1))
+                    .padding(8)
+                    .on_input(move |offset| {
+                        ConfigureMessage::RawFieldUpdated(
+                            i,
+                            RawFieldDefinition {
+                                offset: parse_number_input(&offset, field.offset),
+                                ..field.clone()
+                            },
+                        )
+                    })
+                    .style(rounded_text_input_style)
+                    .into(),
+                text_input("Length", &number_input_value(field.length))
+                    .width(Length::FillPortion(1))
+                    .padding(8)
+                    .on_input(move |length| {
+                        ConfigureMessage::RawFieldUpdated(
+                            i,
+                            RawFieldDefinition {
+                                length: parse_number_input(&length, field.length),
+                                ..field.clone()
+                            },
+                        )
+                    })
+                    .style(rounded_text_input_style)
+                    .into(),
+            ])
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into()
+        }))
+        .spacing(8)
+        .into(),
+        rounded_button(
+            "New raw field",
+            super::utils::RoundedButtonVariant::Secondary,
+        )
+        .on_press(ConfigureMessage::RawFieldAdded)
+        .into(),
+    ])
+    .spacing(8)
+    .into()
+}
+
+/// A mapping item's transformations, one row each in the order they're
+/// applied, with their parameters under them. `source_value` is the item's
+/// source field from the last stream, if any, to preview them with.
+fn transformations_view<'a>(
+    i: usize,
+    transformations: &'a [Transformation],
+    source_value: Option<&Value>,
+    latest_source: &Map<String, Value>,
+) -> Element<'a, ProfileCompositionMessage> {
+    // what each transformation gets from the ones before it
+    let mut input = source_value.cloned();
+    let inputs: Vec<_> = transformations
+        .iter()
+        .map(|transformation| {
+            let next = input
+                .as_ref()
+                .and_then(|value| transformation.transform(value, latest_source).ok());
+            std::mem::replace(&mut input, next)
+        })
+        .collect();
+    column(
+        transformations
+            .iter()
+            .zip(inputs)
+            .enumerate()
+            .map(|(j, (transformation, input))| {
+                let transformation_row = row([
+                    pick_list(Transformation::ALL, Some(transformation), move |new| {
+                        ProfileCompositionMessage::ItemTransformationUpdated(i, j, new)
+                    })
+                    .width(Length::Fill)
+                    .padding(8)
+                    .style(rounded_pick_list_style)
+                    .into(),
+                    Element::from(icon_button(
+                        include_bytes!("../../assets/icon_arrow_upward.svg"),
+                        "Apply earlier",
+                        (j > 0)
+                            .then_some(ProfileCompositionMessage::ItemTransformationMovedUp(i, j)),
+                        super::utils::RoundedButtonVariant::Secondary,
+                    )),
+                    Element::from(icon_button(
+                        include_bytes!("../../assets/icon_arrow_downward.svg"),
+                        "Apply later",
+                        (j + 1 < transformations.len()).then_some(
+                            ProfileCompositionMessage::ItemTransformationMovedUp(i, j + 1),
+                        ),
+                        super::utils::RoundedButtonVariant::Secondary,
+                    )),
+                    Element::from(icon_button(
+                        include_bytes!("../../assets/icon_delete.svg"),
+                        "Remove transformation",
+                        Some(ProfileCompositionMessage::ItemTransformationRemoved(i, j)),
+                        super::utils::RoundedButtonVariant::Danger,
+                    )),
+                ])
+                .spacing(8)
+                .align_y(iced::Alignment::Center);
+                column([transformation_row.into()])
+                    .push_maybe(transformation_editor(i, j, transformation, input))
+                    .spacing(8)
+                    .into()
+            }),
+    )
+    .push(
+        rounded_button(
+            "Add transformation",
+            super::utils::RoundedButtonVariant::Secondary,
+        )
+        .on_press(ProfileCompositionMessage::ItemTransformationAdded(i)),
+    )
+    .spacing(8)
+    .into()
+}
+
+/// The parameters of a transformation that has some, shown under it.
+fn transformation_editor(
+    i: usize,
+    j: usize,
+    transformation: &Transformation,
+    input: Option<Value>,
+) -> Option<Element<'_, ProfileCompositionMessage>> {
+    match transformation {
+        Transformation::Expression(expression) => Some(source_editor(
+            "home_team_score - guest_team_score",
+            expression.source(),
+            expression
+                .parsed()
+                .map(|_| "Refer to source fields by name and to the source field above as value")
+                .map_err(|err| format!("Invalid expression {}", err)),
+            move |new| {
+                ProfileCompositionMessage::ItemTransformationUpdated(
+                    i,
+                    j,
+                    Transformation::Expression(new.into()),
+                )
+            },
+        )),
+        Transformation::Template(template) => Some(source_editor(
+            "Q{period} {main_clock_time}",
+            template.source(),
+            template
+                .parsed()
+                .map(|_| "Put source fields in braces, with transformations after a |, e.g. {period|AppendOrdinalSuffix}")
+                .map_err(|err| format!("Invalid template {}", err)),
+            move |new| {
+                ProfileCompositionMessage::ItemTransformationUpdated(
+                    i,
+                    j,
+                    Transformation::Template(new.into()),
+                )
+            },
+        )),
+        Transformation::Lookup(table) => Some(lookup_table_editor(i, j, table)),
+        Transformation::RegexExtract(extract) => Some(regex_extract_editor(i, j, extract, input)),
+        Transformation::RegexReplace(replace) => Some(regex_replace_editor(i, j, replace, input)),
+        Transformation::ChangeCase(case) => Some(
+            pick_list(TextCase::ALL, Some(case), move |new| {
+                ProfileCompositionMessage::ItemTransformationUpdated(
+                    i,
+                    j,
+                    Transformation::ChangeCase(new),
+                )
+            })
+            .width(Length::Fill)
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+        ),
+        Transformation::Pad(padding) => Some(padding_editor(i, j, *padding)),
+        Transformation::Truncate(truncation) => Some(truncation_editor(i, j, *truncation)),
+        Transformation::ClockDisplay(format) => Some(clock_format_editor(i, j, *format)),
+        Transformation::ZeroPad(digits) => Some(
+            text_input("Whole digits", &number_input_value(*digits))
+                .width(Length::Fill)
+                .padding(8)
+                .on_input(move |new| {
+                    ProfileCompositionMessage::ItemTransformationUpdated(
+                        i,
+                        j,
+                        Transformation::ZeroPad(parse_number_input(&new, *digits)),
+                    )
+                })
+                .style(rounded_text_input_style)
+                .into(),
+        ),
+        _ => None,
+    }
+}
+
+/// The entries of a lookup table, one row each, and what to do with values
+/// that aren't in it.
+fn lookup_table_editor(
+    i: usize,
+    j: usize,
+    table: &LookupTable,
+) -> Element<'_, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut LookupTable)| {
+        let mut table = table.clone();
+        update(&mut table);
+        ProfileCompositionMessage::ItemTransformationUpdated(i, j, Transformation::Lookup(table))
+    };
+    column([
+        column(table.entries.iter().enumerate().map(|(k, entry)| {
+            row([
+                Element::from(icon_button(
+                    include_bytes!("../../assets/icon_delete.svg"),
+                    "Remove entry",
+                    Some(()),
+                    super::utils::RoundedButtonVariant::Danger,
+                ))
+                .map(move |_| {
+                    updated(&|table| {
+                        table.entries.remove(k);
+                    })
+                }),
+                text_input("Console value", &entry.key)
+                    .width(Length::Fill)
+                    .padding(8)
+                    .on_input(move |key| updated(&|table| table.entries[k].key = key.clone()))
+                    .style(rounded_text_input_style)
+                    .into(),
+                text_input("Display text", &entry.value)
+                    .width(Length::Fill)
+                    .padding(8)
+                    .on_input(move |value| updated(&|table| table.entries[k].value = value.clone()))
+                    .style(rounded_text_input_style)
+                    .into(),
+            ])
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into()
+        }))
+        .spacing(8)
+        .into(),
+        row([
+            rounded_button("New entry", super::utils::RoundedButtonVariant::Secondary)
+                .on_press(updated(&|table| table.entries.push(Default::default())))
+                .into(),
+            pick_list(
+                LookupFallback::ALL,
+                Some(&table.fallback),
+                move |fallback| updated(&|table| table.fallback = fallback.clone()),
+            )
+            .width(Length::Fill)
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+        ])
+        .push_maybe(match &table.fallback {
+            LookupFallback::Default(default) => Some(
+                text_input("Default text", default)
+                    .width(Length::Fill)
+                    .padding(8)
+                    .on_input(move |default| {
+                        updated(&|table| table.fallback = LookupFallback::Default(default.clone()))
+                    })
+                    .style(rounded_text_input_style),
+            ),
+            _ => None,
+        })
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+        .into(),
+    ])
+    .spacing(8)
+    .into()
+}
+
+fn padding_editor<'a>(
+    i: usize,
+    j: usize,
+    padding: Padding,
+) -> Element<'a, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut Padding)| {
+        let mut padding = padding;
+        update(&mut padding);
+        ProfileCompositionMessage::ItemTransformationUpdated(i, j, Transformation::Pad(padding))
+    };
+    row([
+        pick_list(PadSide::ALL, Some(padding.side), move |side| {
+            updated(&|padding| padding.side = side)
+        })
+        .width(Length::Fill)
+        .padding(8)
+        .style(rounded_pick_list_style)
+        .into(),
+        text_input("Width", &number_input_value(padding.width))
+            .width(Length::Fill)
+            .padding(8)
+            .on_input(move |width| {
+                updated(&|padding| padding.width = parse_number_input(&width, padding.width))
+            })
+            .style(rounded_text_input_style)
+            .into(),
+        text_input("Character", &padding.character.to_string())
+            .width(Length::Fill)
+            .padding(8)
+            .on_input(move |character| {
+                // the last character typed replaces the old one
+                updated(&|padding| padding.character = character.chars().last().unwrap_or(' '))
+            })
+            .style(rounded_text_input_style)
+            .into(),
+    ])
+    .spacing(8)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+fn truncation_editor<'a>(
+    i: usize,
+    j: usize,
+    truncation: Truncation,
+) -> Element<'a, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut Truncation)| {
+        let mut truncation = truncation;
+        update(&mut truncation);
+        ProfileCompositionMessage::ItemTransformationUpdated(
+            i,
+            j,
+            Transformation::Truncate(truncation),
+        )
+    };
+    row([
+        text_input(
+            "Width (blank for no limit)",
+            &number_input_value(truncation.width),
+        )
+        .width(Length::Fill)
+        .padding(8)
+        .on_input(move |width| {
+            updated(&|truncation| truncation.width = parse_number_input(&width, truncation.width))
+        })
+        .style(rounded_text_input_style)
+        .into(),
+        checkbox("End with … when cut", truncation.ellipsis)
+            .on_toggle(move |ellipsis| updated(&|truncation| truncation.ellipsis = ellipsis))
+            .width(Length::Fill)
+            .into(),
+    ])
+    .spacing(8)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+fn clock_format_editor<'a>(
+    i: usize,
+    j: usize,
+    format: ClockFormat,
+) -> Element<'a, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut ClockFormat)| {
+        let mut format = format;
+        update(&mut format);
+        ProfileCompositionMessage::ItemTransformationUpdated(
+            i,
+            j,
+            Transformation::ClockDisplay(format),
+        )
+    };
+    row([
+        text_input(
+            "Show tenths below (seconds, blank for never)",
+            &number_input_value(format.tenths_below_seconds as usize),
+        )
+        .width(Length::Fill)
+        .padding(8)
+        .on_input(move |seconds| {
+            updated(&|format| {
+                format.tenths_below_seconds =
+                    parse_number_input(&seconds, format.tenths_below_seconds as usize) as u64
+            })
+        })
+        .style(rounded_text_input_style)
+        .into(),
+        checkbox("Show hours (H:MM:SS)", format.show_hours)
+            .on_toggle(move |show_hours| updated(&|format| format.show_hours = show_hours))
+            .width(Length::Fill)
+            .into(),
+        checkbox("Leave out leading zeros", format.suppress_leading_zeros)
+            .on_toggle(move |suppress| updated(&|format| format.suppress_leading_zeros = suppress))
+            .width(Length::Fill)
+            .into(),
+    ])
+    .spacing(8)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+fn regex_extract_editor(
+    i: usize,
+    j: usize,
+    extract: &RegexExtract,
+    input: Option<Value>,
+) -> Element<'_, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut RegexExtract)| {
+        let mut extract = extract.clone();
+        update(&mut extract);
+        ProfileCompositionMessage::ItemTransformationUpdated(
+            i,
+            j,
+            Transformation::RegexExtract(extract),
+        )
+    };
+    let valid = extract.validate();
+    column([
+        row([
+            text_input("#(\\d+)", extract.pattern.source())
+                .width(Length::FillPortion(3))
+                .padding(8)
+                .on_input(move |pattern| {
+                    updated(&|extract| extract.pattern = pattern.clone().into())
+                })
+                .style(rounded_text_input_style)
+                .into(),
+            text_input(
+                "Group (blank for the whole match)",
+                &number_input_value(extract.group),
+            )
+            .width(Length::FillPortion(1))
+            .padding(8)
+            .on_input(move |group| {
+                updated(&|extract| extract.group = parse_number_input(&group, extract.group))
+            })
+            .style(rounded_text_input_style)
+            .into(),
+        ])
+        .spacing(8)
+        .into(),
+        regex_status(
+            valid
+                .clone()
+                .map(|_| "Extracts the capture group from the first match"),
+        ),
+        regex_preview(
+            &extract.sample,
+            input,
+            valid.is_ok(),
+            |value| extract.extract(value),
+            move |sample| updated(&|extract| extract.sample = sample.clone()),
+        ),
+    ])
+    .spacing(4)
+    .into()
+}
+
+fn regex_replace_editor(
+    i: usize,
+    j: usize,
+    replace: &RegexReplace,
+    input: Option<Value>,
+) -> Element<'_, ProfileCompositionMessage> {
+    let updated = move |update: &dyn Fn(&mut RegexReplace)| {
+        let mut replace = replace.clone();
+        update(&mut replace);
+        ProfileCompositionMessage::ItemTransformationUpdated(
+            i,
+            j,
+            Transformation::RegexReplace(replace),
+        )
+    };
+    let valid = replace.validate();
+    column([
+        row([
+            text_input("\\s+", replace.pattern.source())
+                .width(Length::Fill)
+                .padding(8)
+                .on_input(move |pattern| {
+                    updated(&|replace| replace.pattern = pattern.clone().into())
+                })
+                .style(rounded_text_input_style)
+                .into(),
+            text_input("Replacement", &replace.replacement)
+                .width(Length::Fill)
+                .padding(8)
+                .on_input(move |replacement| {
+                    updated(&|replace| replace.replacement = replacement.clone())
+                })
+                .style(rounded_text_input_style)
+                .into(),
+        ])
+        .spacing(8)
+        .into(),
+        regex_status(
+            valid
+                .clone()
+                .map(|_| "Replaces every match; refer to capture groups like $1"),
+        ),
+        regex_preview(
+            &replace.sample,
+            input,
+            valid.is_ok(),
+            |value| replace.replace(value),
+            move |sample| updated(&|replace| replace.sample = sample.clone()),
+        ),
+    ])
+    .spacing(4)
+    .into()
+}
+
+/// A hint about a regex transformation, or why its pattern is invalid.
+fn regex_status<'a>(
+    status: Result<&'static str, String>,
+) -> Element<'a, ProfileCompositionMessage> {
+    match status {
+        Ok(hint) => text(hint)
+            .style(|theme: &Theme| text::Style {
+                color: Some(theme.palette().text.scale_alpha(0.6)),
+            })
+            .into(),
+        Err(err) => text(format!("Invalid regex: {}", err))
+            .style(text::danger)
+            .into(),
+    }
+}
+
+/// What a regex transformation makes of the data from the last stream, or of
+/// sample text if the stream didn't have the source field.
+fn regex_preview<'a>(
+    sample: &'a str,
+    input: Option<Value>,
+    valid: bool,
+    transform: impl Fn(&Value) -> Result<Value, TransformationError>,
+    on_sample_input: impl Fn(String) -> ProfileCompositionMessage + 'a,
+) -> Element<'a, ProfileCompositionMessage> {
+    let (label, input) = match input {
+        Some(input) => ("Latest data", Some(input)),
+        None => (
+            "Sample",
+            (!sample.is_empty()).then(|| Value::String(sample.to_owned())),
+        ),
+    };
+    let preview = input.filter(|_| valid).map(|input| {
+        let result = match transform(&input) {
+            Ok(Value::String(output)) => Ok(format!("{:?}", output)),
+            Ok(output) => Ok(output.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        (input, result)
+    });
+    column([text_input("Sample text to preview with", sample)
+        .width(Length::Fill)
+        .padding(8)
+        .on_input(on_sample_input)
+        .style(rounded_text_input_style)
+        .into()])
+    .push_maybe(preview.map(
+        |(input, result)| -> Element<'a, ProfileCompositionMessage> {
+            match result {
+                Ok(output) => text(format!("{} {} becomes {}", label, input, output))
+                    .style(|theme: &Theme| text::Style {
+                        color: Some(theme.palette().text.scale_alpha(0.6)),
+                    })
+                    .into(),
+                Err(err) => text(format!("{} {}: {}", label, input, err))
+                    .style(text::danger)
+                    .into(),
+            }
+        },
+    ))
+    .spacing(4)
+    .into()
+}
+
+/// A text input for a transformation's source, with a hint below it or why it
+/// doesn't parse.
+fn source_editor<'a>(
+    placeholder: &str,
+    source: &'a str,
+    status: Result<&'static str, String>,
+    on_input: impl Fn(String) -> ProfileCompositionMessage + 'a,
+) -> Element<'a, ProfileCompositionMessage> {
+    column([
+        text_input(placeholder, source)
+            .width(Length::Fill)
+            .padding(8)
+            .on_input(on_input)
+            .style(rounded_text_input_style)
+            .into(),
+        match status {
+            Ok(hint) => text(hint)
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.palette().text.scale_alpha(0.6)),
+                })
+                .into(),
+            Err(err) => text(err).style(text::danger).into(),
+        },
+    ])
+    .spacing(4)
+    .into()
+}
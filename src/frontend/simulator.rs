@@ -0,0 +1,239 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use iced::{
+    widget::{column, pick_list, row, scrollable, text, text_input},
+    Element, Font, Length, Renderer, Subscription, Theme,
+};
+
+use crate::backend::{
+    mapping::clock::{ClockFormat, ClockTime},
+    simulator::{SimulatorFeed, SportLayout},
+};
+
+use super::utils::{
+    icon_button, rounded_button, rounded_pane, rounded_pick_list_style, rounded_text_input_style,
+};
+
+const CLOCK_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Hand-editable scoreboard values that are sent to the stream as if they came
+/// from a console.
+#[derive(Debug)]
+pub struct Simulator {
+    feed: SimulatorFeed,
+    layout: Option<Result<SportLayout, String>>,
+    values: BTreeMap<String, String>,
+    clock_field: Option<String>,
+    clock_input: String,
+    clock_remaining: Duration,
+    /// When the clock last ticked, if it's running
+    clock_last_tick: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SimulatorMessage {
+    LayoutProbed(Result<SportLayout, String>),
+    FieldEdited(String, String),
+    ClockFieldPicked(String),
+    ClockInputUpdated(String),
+    ClockSet,
+    ClockToggled,
+    ClockTick(Instant),
+}
+
+/// Formats the clock like the console does: `M:SS` above a minute (`H:MM:SS`
+/// above an hour) and `S.t` below.
+const CLOCK_FORMAT: ClockFormat = ClockFormat {
+    tenths_below_seconds: 60,
+    show_hours: true,
+    suppress_leading_zeros: true,
+};
+
+impl Simulator {
+    pub fn new(feed: SimulatorFeed) -> Self {
+        Self {
+            feed,
+            layout: None,
+            values: BTreeMap::new(),
+            clock_field: None,
+            clock_input: String::new(),
+            clock_remaining: Duration::ZERO,
+            clock_last_tick: None,
+        }
+    }
+
+    /// How many fields can be edited, once the layout is known.
+    pub fn field_count(&self) -> usize {
+        match self.layout {
+            Some(Ok(ref layout)) => layout.fields.len(),
+            _ => 0,
+        }
+    }
+
+    /// Sends the field's value to the stream if the field's layout is known.
+    fn send_field(&self, field: &str) {
+        if let Some(Ok(layout)) = &self.layout {
+            if let (Some(field_layout), Some(value)) =
+                (layout.fields.get(field), self.values.get(field))
+            {
+                self.feed.send(field_layout.encode(value));
+            }
+        }
+    }
+
+    fn set_clock(&mut self, remaining: Duration) {
+        self.clock_remaining = remaining;
+        if let Some(clock_field) = self.clock_field.clone() {
+            self.values
+                .insert(clock_field.clone(), CLOCK_FORMAT.format(remaining.into()));
+            self.send_field(&clock_field);
+        }
+    }
+
+    pub fn update(&mut self, message: SimulatorMessage) {
+        match message {
+            SimulatorMessage::LayoutProbed(layout) => {
+                if let Ok(ref layout) = layout {
+                    self.clock_field = layout
+                        .fields
+                        .keys()
+                        .find(|key| key.contains("clock"))
+                        .cloned();
+                }
+                self.layout = Some(layout);
+            }
+            SimulatorMessage::FieldEdited(field, value) => {
+                self.values.insert(field.clone(), value);
+                self.send_field(&field);
+            }
+            SimulatorMessage::ClockFieldPicked(field) => {
+                self.clock_field = Some(field);
+            }
+            SimulatorMessage::ClockInputUpdated(input) => {
+                self.clock_input = input;
+            }
+            SimulatorMessage::ClockSet => {
+                // the same formats as the clock transformations read
+                if let Ok(Some(remaining)) = ClockTime::parse(&self.clock_input) {
+                    self.set_clock(remaining.into());
+                }
+            }
+            SimulatorMessage::ClockToggled => {
+                self.clock_last_tick = match self.clock_last_tick {
+                    Some(_) => None,
+                    None => Some(Instant::now()),
+                };
+            }
+            SimulatorMessage::ClockTick(now) => {
+                if let Some(last_tick) = self.clock_last_tick {
+                    let remaining = self
+                        .clock_remaining
+                        .saturating_sub(now.saturating_duration_since(last_tick));
+                    self.clock_last_tick = (!remaining.is_zero()).then_some(now);
+                    self.set_clock(remaining);
+                }
+            }
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<SimulatorMessage> {
+        if self.clock_last_tick.is_some() {
+            iced::time::every(CLOCK_TICK_INTERVAL).map(SimulatorMessage::ClockTick)
+        } else {
+            Subscription::none()
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, SimulatorMessage, Theme, Renderer> {
+        let content: Element<'_, SimulatorMessage, Theme, Renderer> = match &self.layout {
+            None => text("Working out where each field lives...").into(),
+            Some(Err(err)) => text(format!("Couldn't work out the field layout: {}", err))
+                .style(text::danger)
+                .into(),
+            Some(Ok(layout)) => column([
+                row([
+                    pick_list(
+                        layout.fields.keys().cloned().collect::<Vec<_>>(),
+                        self.clock_field.clone(),
+                        SimulatorMessage::ClockFieldPicked,
+                    )
+                    .placeholder("Clock field")
+                    .padding(8)
+                    .width(Length::Fill)
+                    .style(rounded_pick_list_style)
+                    .into(),
+                    text_input("12:00", &self.clock_input)
+                        .on_input(SimulatorMessage::ClockInputUpdated)
+                        .on_submit(SimulatorMessage::ClockSet)
+                        .padding(8)
+                        .width(80)
+                        .style(rounded_text_input_style)
+                        .into(),
+                    rounded_button("Set", super::utils::RoundedButtonVariant::Secondary)
+                        .on_press(SimulatorMessage::ClockSet)
+                        .into(),
+                    icon_button(
+                        if self.clock_last_tick.is_some() {
+                            include_bytes!("../../assets/icon_stop_circle.svg")
+                        } else {
+                            include_bytes!("../../assets/icon_play_circle.svg")
+                        },
+                        if self.clock_last_tick.is_some() {
+                            "Stop clock"
+                        } else {
+                            "Start clock"
+                        },
+                        self.clock_field
+                            .is_some()
+                            .then_some(SimulatorMessage::ClockToggled),
+                        super::utils::RoundedButtonVariant::Primary,
+                    )
+                    .into(),
+                ])
+                .spacing(4)
+                .align_y(iced::Alignment::Center)
+                .into(),
+                text(CLOCK_FORMAT.format(self.clock_remaining.into()))
+                    .font(Font::MONOSPACE)
+                    .size(24)
+                    .into(),
+                scrollable(
+                    column(layout.fields.keys().map(|field| {
+                        row([
+                            text(field)
+                                .font(Font::MONOSPACE)
+                                .width(Length::FillPortion(1))
+                                .into(),
+                            text_input(
+                                "",
+                                self.values.get(field).map(String::as_str).unwrap_or(""),
+                            )
+                            .on_input(move |value| {
+                                SimulatorMessage::FieldEdited(field.clone(), value)
+                            })
+                            .padding(4)
+                            .width(Length::FillPortion(1))
+                            .style(rounded_text_input_style)
+                            .into(),
+                        ])
+                        .spacing(8)
+                        .align_y(iced::Alignment::Center)
+                        .into()
+                    }))
+                    .spacing(4),
+                )
+                .height(Length::Fill)
+                .into(),
+            ])
+            .spacing(8)
+            .into(),
+        };
+        rounded_pane(column([content]).padding(16))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+}
@@ -1,23 +1,40 @@
+use std::time::Duration;
+
 use iced::{
-    widget::{column, container, horizontal_space, row, scrollable, text},
-    Element, Font, Length, Padding, Renderer, Theme,
+    widget::{column, container, horizontal_space, row, scrollable, svg, text, text_input},
+    Alignment, Border, Element, Font, Length, Padding, Renderer, Subscription, Theme,
 };
 
-use crate::backend::stream::ActiveStream;
+use crate::backend::{
+    simulator::SimulatorFeed,
+    stream::{source::ConnectionState, ActiveStream},
+};
 
 use super::{
     graph::Graph,
-    utils::{icon_button, rounded_pane},
+    simulator::{Simulator, SimulatorMessage},
+    utils::{icon_button, rounded_pane, rounded_text_input_style},
 };
 
-#[derive(Debug, Clone, Copy)]
+/// How long to show that the input reconnected after it did.
+const RECONNECTED_BANNER_DURATION: Duration = Duration::from_secs(10);
+/// How long a raw field stays highlighted after its value changes.
+const RAW_FIELD_HIGHLIGHT_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
 pub struct StreamRunning {
-    _no_public_constructor: (),
+    simulator: Option<Simulator>,
+    raw_field_filter: String,
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug)]
 pub enum StreamRunningMessage {
     ClearErrors,
+    ToggleRecording,
+    StepReplay,
+    RawFieldFilterUpdated(String),
+    CopyRawFields,
+    Simulator(SimulatorMessage),
 }
 
 fn pane_header<'a, Message: 'a>(
@@ -54,22 +71,110 @@ fn pane_header<'a, Message: 'a>(
     .into()
 }
 
+/// A one-line notice above the panes, in the danger color if `danger`.
+fn banner<'a>(message: String, danger: bool) -> Element<'a, StreamRunningMessage, Theme, Renderer> {
+    container(
+        container(
+            row([
+                container(
+                    svg(svg::Handle::from_memory(if danger {
+                        include_bytes!("../../assets/icon_warning.svg").as_slice()
+                    } else {
+                        include_bytes!("../../assets/icon_check_box.svg").as_slice()
+                    }))
+                    .style(move |theme: &Theme, _| svg::Style {
+                        color: Some(if danger {
+                            theme.palette().danger
+                        } else {
+                            theme.palette().primary
+                        }),
+                    })
+                    .content_fit(iced::ContentFit::Fill),
+                )
+                .width(24)
+                .height(24)
+                .into(),
+                text(message)
+                    .style(move |theme: &Theme| {
+                        if danger {
+                            text::danger(theme)
+                        } else {
+                            text::primary(theme)
+                        }
+                    })
+                    .into(),
+            ])
+            .align_y(Alignment::Center)
+            .spacing(8),
+        )
+        .style(move |theme: &Theme| container::Style {
+            border: Border {
+                color: if danger {
+                    theme.palette().danger
+                } else {
+                    theme.palette().primary
+                },
+                width: 1.0,
+                radius: 999.into(),
+            },
+            ..Default::default()
+        })
+        .padding([8, 16]),
+    )
+    .padding(Padding::new(16.0).top(0.0))
+    .into()
+}
+
 pub enum Update {
-    #[allow(dead_code)]
     None,
     ClearErrors,
+    ToggleRecording,
+    StepReplay,
+    CopyRawFields,
 }
 
 impl StreamRunning {
     pub fn new() -> Self {
         Self {
-            _no_public_constructor: (),
+            simulator: None,
+            raw_field_filter: String::new(),
+        }
+    }
+
+    /// Shows the simulator alongside the stream stats, sending its values to
+    /// the given feed.
+    pub fn with_simulator(feed: SimulatorFeed) -> Self {
+        Self {
+            simulator: Some(Simulator::new(feed)),
+            raw_field_filter: String::new(),
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<StreamRunningMessage> {
+        match self.simulator {
+            Some(ref simulator) => simulator
+                .subscription()
+                .map(StreamRunningMessage::Simulator),
+            None => Subscription::none(),
         }
     }
 
     pub fn update(&mut self, message: StreamRunningMessage) -> Update {
         match message {
             StreamRunningMessage::ClearErrors => Update::ClearErrors,
+            StreamRunningMessage::ToggleRecording => Update::ToggleRecording,
+            StreamRunningMessage::StepReplay => Update::StepReplay,
+            StreamRunningMessage::RawFieldFilterUpdated(new_filter) => {
+                self.raw_field_filter = new_filter;
+                Update::None
+            }
+            StreamRunningMessage::CopyRawFields => Update::CopyRawFields,
+            StreamRunningMessage::Simulator(message) => {
+                if let Some(ref mut simulator) = self.simulator {
+                    simulator.update(message);
+                }
+                Update::None
+            }
         }
     }
 
@@ -87,8 +192,21 @@ impl StreamRunning {
                     .map(|x| x.latency.as_millis().try_into().unwrap_or(i32::MAX))
                     .unwrap_or(0),
                 "ms",
-                // not sure why Rust needs annotations but whatever
-                Option::<&str>::None,
+                Some(if active_stream.recorder().path().is_some() {
+                    icon_button(
+                        include_bytes!("../../assets/icon_stop_circle.svg"),
+                        "Stop recording raw traffic",
+                        Some(StreamRunningMessage::ToggleRecording),
+                        super::utils::RoundedButtonVariant::Danger,
+                    )
+                } else {
+                    icon_button(
+                        include_bytes!("../../assets/icon_radio_button_checked.svg"),
+                        "Record raw traffic to a capture file",
+                        Some(StreamRunningMessage::ToggleRecording),
+                        super::utils::RoundedButtonVariant::Secondary,
+                    )
+                }),
             ),
             rounded_pane(
                 container(Graph::new(&active_stream).into_view())
@@ -100,6 +218,81 @@ impl StreamRunning {
             .height(Length::Fill)
             .into(),
         ]);
+        let raw_fields_pane = {
+            let filter = self.raw_field_filter.to_lowercase();
+            column([
+                pane_header(
+                    "Raw fields",
+                    active_stream
+                        .raw_fields()
+                        .len()
+                        .try_into()
+                        .unwrap_or(i32::MAX),
+                    "fields",
+                    Some(icon_button(
+                        include_bytes!("../../assets/icon_content_copy.svg"),
+                        "Copy snapshot",
+                        Some(StreamRunningMessage::CopyRawFields),
+                        super::utils::RoundedButtonVariant::Secondary,
+                    )),
+                ),
+                rounded_pane(
+                    column([
+                        text_input("Filter fields", &self.raw_field_filter)
+                            .on_input(StreamRunningMessage::RawFieldFilterUpdated)
+                            .padding(8)
+                            .style(rounded_text_input_style)
+                            .into(),
+                        scrollable(
+                            column(
+                                active_stream
+                                    .raw_fields()
+                                    .iter()
+                                    .filter(|(key, _)| key.to_lowercase().contains(&filter))
+                                    .map(|(key, field)| {
+                                        let highlighted =
+                                            field.changed.elapsed() < RAW_FIELD_HIGHLIGHT_DURATION;
+                                        container(
+                                            row([
+                                                text(key)
+                                                    .font(Font::MONOSPACE)
+                                                    .width(Length::FillPortion(1))
+                                                    .into(),
+                                                text(match field.value {
+                                                    serde_json::Value::String(ref value) => {
+                                                        value.clone()
+                                                    }
+                                                    ref value => value.to_string(),
+                                                })
+                                                .font(Font::MONOSPACE)
+                                                .width(Length::FillPortion(1))
+                                                .into(),
+                                            ])
+                                            .spacing(8),
+                                        )
+                                        .style(move |theme: &Theme| container::Style {
+                                            background: highlighted.then(|| {
+                                                theme.palette().primary.scale_alpha(0.25).into()
+                                            }),
+                                            ..Default::default()
+                                        })
+                                        .padding([2, 4])
+                                        .into()
+                                    }),
+                            )
+                            .width(Length::Fill),
+                        )
+                        .height(Length::Fill)
+                        .into(),
+                    ])
+                    .spacing(8)
+                    .padding(16),
+                )
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .into(),
+            ])
+        };
         let payload_pane = {
             column([
                 pane_header(
@@ -110,9 +303,14 @@ impl StreamRunning {
                         .try_into()
                         .unwrap_or(i32::MAX),
                     "B",
-                    // not sure why Rust needs annotations but whatever
-                    // &str can be `into`'ed into an Element, so it works
-                    Option::<&str>::None,
+                    active_stream.replay_control().map(|_| {
+                        icon_button(
+                            include_bytes!("../../assets/icon_play_circle.svg"),
+                            "Replay next packet",
+                            Some(StreamRunningMessage::StepReplay),
+                            super::utils::RoundedButtonVariant::Primary,
+                        )
+                    }),
                 ),
                 rounded_pane(scrollable(
                     container(
@@ -160,10 +358,55 @@ impl StreamRunning {
                 .into(),
             ])
         });
-        row([latency_pane.into(), payload_pane.into()])
-            .push_maybe(error_pane)
-            .spacing(12)
-            .padding(Padding::new(16.0).top(0.0))
+        let simulator_pane = self.simulator.as_ref().map(|simulator| {
+            column([
+                pane_header(
+                    "Simulator",
+                    simulator.field_count().try_into().unwrap_or(i32::MAX),
+                    "fields",
+                    Option::<Element<StreamRunningMessage, Theme, Renderer>>::None,
+                ),
+                simulator.view().map(StreamRunningMessage::Simulator),
+            ])
+        });
+        let connection_banner = match active_stream.connection_state() {
+            (ConnectionState::Disconnected(reason), _) => Some(banner(
+                format!(
+                    "Lost connection to the console ({}). Reconnecting...",
+                    reason
+                ),
+                true,
+            )),
+            (ConnectionState::Reconnected(input), timestamp)
+                if timestamp.elapsed() < RECONNECTED_BANNER_DURATION =>
+            {
+                Some(banner(format!("Reconnected to {}", input), false))
+            }
+            _ => None,
+        };
+        let sport_warning = active_stream.sport_mismatch().map(|detected| {
+            banner(
+                format!(
+                    "The console looks like it's set to {}, which doesn't match the profile's sport. Check the sport in the Configure tab.",
+                    detected
+                ),
+                true,
+            )
+        });
+        column([])
+            .push_maybe(connection_banner)
+            .push_maybe(sport_warning)
+            .push(
+                row(simulator_pane.map(Element::from))
+                    .push(latency_pane)
+                    .push(raw_fields_pane)
+                    .push(payload_pane)
+                    .push_maybe(error_pane)
+                    .spacing(12)
+                    .padding(Padding::new(16.0).top(0.0))
+                    .width(Length::Fill)
+                    .height(Length::Fill),
+            )
             .width(Length::Fill)
             .height(Length::Fill)
             .into()
@@ -1,12 +1,26 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
 use iced::{
-    widget::{column, container, pick_list, row, svg, text},
-    Alignment, Border, Length, Shadow, Theme,
+    widget::{checkbox, column, container, pick_list, row, svg, text, text_input},
+    Alignment, Border, Element, Length, Shadow, Theme,
 };
 use tokio_serial::SerialPortInfo;
 
-use super::utils::{icon_button, rounded_pick_list_style};
+use crate::backend::{
+    simulator::SimulatorFeed,
+    stream::{
+        replay::{ReplayOptions, ReplaySpeed},
+        serial::{
+            DataBits, FlowControl, Parity, PortScore, SerialSettings, StopBits,
+            COMMON_BAUD_RATES,
+        },
+        source::StreamInput,
+    },
+};
+
+use super::utils::{
+    icon_button, rounded_button, rounded_pick_list_style, rounded_text_input_style,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SerialPortInfoWrapper(SerialPortInfo);
@@ -58,10 +72,49 @@ fn enumerate_ports() -> Vec<SerialPortInfoWrapper> {
     ports
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Serial,
+    Tcp,
+    Udp,
+    Replay,
+    Simulator,
+}
+
+impl InputKind {
+    const ALL: [InputKind; 5] = [
+        InputKind::Serial,
+        InputKind::Tcp,
+        InputKind::Udp,
+        InputKind::Replay,
+        InputKind::Simulator,
+    ];
+}
+
+impl Display for InputKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InputKind::Serial => "Serial port",
+            InputKind::Tcp => "Network (TCP)",
+            InputKind::Udp => "Network (UDP)",
+            InputKind::Replay => "Replay capture",
+            InputKind::Simulator => "Simulator",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct StreamStart {
     serial_ports: Vec<SerialPortInfoWrapper>,
     selected_serial_port: Option<SerialPortInfoWrapper>,
+    input_kind: InputKind,
+    network_address: String,
+    replay_path: Option<PathBuf>,
+    replay_speed: ReplaySpeed,
+    replay_looping: bool,
+    detecting_serial_port: bool,
+    /// What the last serial port detection found, best first
+    port_scores: Option<Vec<PortScore>>,
 }
 
 #[derive(Debug, Clone)]
@@ -69,11 +122,23 @@ pub enum StreamStartMessage {
     StartStream,
     SerialPortPicked(SerialPortInfoWrapper),
     RefreshSerialPorts,
+    DetectSerialPort,
+    SerialPortsDetected(Vec<PortScore>),
+    InputKindPicked(InputKind),
+    NetworkAddressUpdated(String),
+    PickCapture,
+    CapturePicked(PathBuf),
+    ReplaySpeedPicked(ReplaySpeed),
+    ReplayLoopingToggled(bool),
+    SerialSettingsUpdated(SerialSettings),
 }
 
 pub enum Update {
     None,
-    StartStream { port: String },
+    StartStream { input: StreamInput },
+    PickCapture,
+    DetectSerialPort,
+    SerialSettingsUpdated(SerialSettings),
 }
 
 impl StreamStart {
@@ -81,13 +146,210 @@ impl StreamStart {
         Self {
             selected_serial_port: None,
             serial_ports: enumerate_ports(),
+            input_kind: InputKind::Serial,
+            network_address: String::new(),
+            replay_path: None,
+            replay_speed: ReplaySpeed::RealTime,
+            replay_looping: false,
+            detecting_serial_port: false,
+            port_scores: None,
+        }
+    }
+
+    /// The input the stream would be started with, if enough has been filled
+    /// in to start one.
+    fn input(&self) -> Option<StreamInput> {
+        match self.input_kind {
+            InputKind::Serial => self
+                .selected_serial_port
+                .as_ref()
+                .map(|port| StreamInput::Serial(port.0.port_name.clone())),
+            InputKind::Tcp => format!("tcp://{}", self.network_address).parse().ok(),
+            InputKind::Udp => format!("udp://{}", self.network_address).parse().ok(),
+            InputKind::Replay => self.replay_path.as_ref().map(|path| {
+                StreamInput::Replay(ReplayOptions {
+                    path: path.clone(),
+                    speed: self.replay_speed,
+                    looping: self.replay_looping,
+                })
+            }),
+            InputKind::Simulator => Some(StreamInput::Simulator(SimulatorFeed::new())),
         }
     }
 
+    fn can_start(&self) -> bool {
+        match self.input_kind {
+            InputKind::Serial => self.selected_serial_port.is_some(),
+            InputKind::Replay => self.replay_path.is_some(),
+            InputKind::Simulator => true,
+            InputKind::Tcp | InputKind::Udp => self.input().is_some(),
+        }
+    }
+
+    fn input_picker(&self) -> Vec<Element<'_, StreamStartMessage>> {
+        match self.input_kind {
+            InputKind::Serial => vec![
+                pick_list(
+                    self.serial_ports.clone(), // TODO: would be good to not clone this all the time but compiler errors...
+                    self.selected_serial_port.clone(), // TODO: this too
+                    StreamStartMessage::SerialPortPicked,
+                )
+                .placeholder("Serial port")
+                .padding(8)
+                .width(300)
+                .style(rounded_pick_list_style)
+                .into(),
+                icon_button(
+                    include_bytes!("../../assets/icon_refresh.svg"),
+                    "Refresh ports",
+                    Some(StreamStartMessage::RefreshSerialPorts),
+                    super::utils::RoundedButtonVariant::Secondary,
+                )
+                .into(),
+                rounded_button(
+                    if self.detecting_serial_port {
+                        "Listening..."
+                    } else {
+                        "Auto-detect"
+                    },
+                    super::utils::RoundedButtonVariant::Secondary,
+                )
+                .on_press_maybe(
+                    (!self.detecting_serial_port).then_some(StreamStartMessage::DetectSerialPort),
+                )
+                .padding(8)
+                .into(),
+            ],
+            InputKind::Tcp | InputKind::Udp => vec![text_input(
+                if matches!(self.input_kind, InputKind::Tcp) {
+                    "Device server host:port"
+                } else {
+                    "Local address:port, e.g. 0.0.0.0:4001"
+                },
+                &self.network_address,
+            )
+            .on_input(StreamStartMessage::NetworkAddressUpdated)
+            .padding(8)
+            .width(340)
+            .style(rounded_text_input_style)
+            .into()],
+            InputKind::Replay => vec![
+                rounded_button(
+                    text(
+                        self.replay_path
+                            .as_ref()
+                            .and_then(|path| path.file_name())
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| "Choose capture...".to_owned()),
+                    ),
+                    super::utils::RoundedButtonVariant::Secondary,
+                )
+                .on_press(StreamStartMessage::PickCapture)
+                .padding(8)
+                .into(),
+                pick_list(
+                    ReplaySpeed::ALL,
+                    Some(self.replay_speed),
+                    StreamStartMessage::ReplaySpeedPicked,
+                )
+                .padding(8)
+                .style(rounded_pick_list_style)
+                .into(),
+                checkbox("Loop", self.replay_looping)
+                    .on_toggle(StreamStartMessage::ReplayLoopingToggled)
+                    .into(),
+            ],
+            InputKind::Simulator => vec![text("Enter scoreboard values by hand")
+                .width(300)
+                .into()],
+        }
+    }
+
+    /// A summary of the last serial port detection, if there was one.
+    fn port_detection_summary(&self) -> Option<String> {
+        let scores = self.port_scores.as_ref()?;
+        Some(match scores.first() {
+            Some(best) if best.valid_packets > 0 => format!(
+                "Found the console on {} ({} packets{})",
+                best.port_name,
+                best.valid_packets,
+                scores
+                    .get(1)
+                    .filter(|runner_up| runner_up.valid_packets > 0)
+                    .map(|runner_up| format!(
+                        ", {} on {}",
+                        runner_up.valid_packets, runner_up.port_name
+                    ))
+                    .unwrap_or_default()
+            ),
+            _ => "No packets on any port. Check the cable and serial settings.".to_owned(),
+        })
+    }
+
+    /// The profile's serial line settings, shown when reading from a serial
+    /// port. Changes are saved to the profile.
+    fn serial_settings_picker(
+        &self,
+        settings: SerialSettings,
+    ) -> Element<'_, StreamStartMessage> {
+        row([
+            pick_list(COMMON_BAUD_RATES, Some(settings.baud_rate), move |baud_rate| {
+                StreamStartMessage::SerialSettingsUpdated(SerialSettings {
+                    baud_rate,
+                    ..settings
+                })
+            })
+            .padding(8)
+            .width(110)
+            .style(rounded_pick_list_style)
+            .into(),
+            pick_list(DataBits::ALL, Some(settings.data_bits), move |data_bits| {
+                StreamStartMessage::SerialSettingsUpdated(SerialSettings {
+                    data_bits,
+                    ..settings
+                })
+            })
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+            pick_list(Parity::ALL, Some(settings.parity), move |parity| {
+                StreamStartMessage::SerialSettingsUpdated(SerialSettings { parity, ..settings })
+            })
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+            pick_list(StopBits::ALL, Some(settings.stop_bits), move |stop_bits| {
+                StreamStartMessage::SerialSettingsUpdated(SerialSettings {
+                    stop_bits,
+                    ..settings
+                })
+            })
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+            pick_list(
+                FlowControl::ALL,
+                Some(settings.flow_control),
+                move |flow_control| {
+                    StreamStartMessage::SerialSettingsUpdated(SerialSettings {
+                        flow_control,
+                        ..settings
+                    })
+                },
+            )
+            .padding(8)
+            .style(rounded_pick_list_style)
+            .into(),
+        ])
+        .spacing(4)
+        .into()
+    }
+
     pub fn view<'a>(
         &'a self,
         error: Option<&'a str>,
         profile_is_dirty: bool,
+        serial_settings: SerialSettings,
     ) -> iced::Element<'a, StreamStartMessage> {
         iced::widget::stack([
             container(
@@ -112,37 +374,36 @@ impl StreamStart {
                         })
                         .size(32)
                         .into(),
-                    row([
-                        pick_list(
-                            self.serial_ports.clone(), // TODO: would be good to not clone this all the time but compiler errors...
-                            self.selected_serial_port.clone(), // TODO: this too
-                            StreamStartMessage::SerialPortPicked,
-                        )
-                        .placeholder("Serial port")
-                        .padding(8)
-                        .width(300)
-                        .style(rounded_pick_list_style)
-                        .into(),
-                        icon_button(
-                            include_bytes!("../../assets/icon_refresh.svg"),
-                            "Refresh ports",
-                            Some(StreamStartMessage::RefreshSerialPorts),
-                            super::utils::RoundedButtonVariant::Secondary,
-                        )
-                        .into(),
-                        icon_button(
-                            include_bytes!("../../assets/icon_play_circle.svg"),
-                            "Start stream",
-                            self.selected_serial_port
-                                .is_some()
-                                .then_some(StreamStartMessage::StartStream),
-                            super::utils::RoundedButtonVariant::Secondary,
-                        )
-                        .into(),
-                    ])
+                    row([pick_list(
+                        InputKind::ALL,
+                        Some(self.input_kind),
+                        StreamStartMessage::InputKindPicked,
+                    )
+                    .padding(8)
+                    .width(160)
+                    .style(rounded_pick_list_style)
+                    .into()])
+                    .extend(self.input_picker())
+                    .push(icon_button(
+                        include_bytes!("../../assets/icon_play_circle.svg"),
+                        "Start stream",
+                        self.can_start()
+                            .then_some(StreamStartMessage::StartStream),
+                        super::utils::RoundedButtonVariant::Secondary,
+                    ))
                     .spacing(4)
                     .into(),
                 ])
+                .push_maybe(
+                    matches!(self.input_kind, InputKind::Serial)
+                        .then(|| self.serial_settings_picker(serial_settings)),
+                )
+                .push_maybe(
+                    matches!(self.input_kind, InputKind::Serial)
+                        .then(|| self.port_detection_summary())
+                        .flatten()
+                        .map(text),
+                )
                 .push_maybe(error.map(|error| {
                     container(
                         row([
@@ -224,13 +485,9 @@ impl StreamStart {
     pub fn update<'a>(&mut self, message: StreamStartMessage) -> Update {
         match message {
             StreamStartMessage::StartStream => Update::StartStream {
-                port: self
-                    .selected_serial_port
-                    .as_ref()
-                    .expect("tty port should be selected before StartStream callback is run")
-                    .0
-                    .port_name
-                    .clone(),
+                input: self
+                    .input()
+                    .expect("input should be filled in before StartStream callback is run"),
             },
             StreamStartMessage::RefreshSerialPorts => {
                 self.serial_ports = enumerate_ports();
@@ -240,6 +497,54 @@ impl StreamStart {
                 self.selected_serial_port = Some(new_port);
                 Update::None
             }
+            StreamStartMessage::DetectSerialPort => {
+                self.detecting_serial_port = true;
+                Update::DetectSerialPort
+            }
+            StreamStartMessage::SerialPortsDetected(scores) => {
+                self.detecting_serial_port = false;
+                let rank = |port: &SerialPortInfoWrapper| {
+                    scores
+                        .iter()
+                        .position(|score| score.port_name == port.0.port_name)
+                        .unwrap_or(scores.len())
+                };
+                self.serial_ports = enumerate_ports();
+                self.serial_ports.sort_by_key(rank);
+                if let Some(best) = scores.first().filter(|best| best.valid_packets > 0) {
+                    self.selected_serial_port = self
+                        .serial_ports
+                        .iter()
+                        .find(|port| port.0.port_name == best.port_name)
+                        .cloned();
+                }
+                self.port_scores = Some(scores);
+                Update::None
+            }
+            StreamStartMessage::InputKindPicked(new_kind) => {
+                self.input_kind = new_kind;
+                Update::None
+            }
+            StreamStartMessage::NetworkAddressUpdated(new_address) => {
+                self.network_address = new_address;
+                Update::None
+            }
+            StreamStartMessage::PickCapture => Update::PickCapture,
+            StreamStartMessage::CapturePicked(path) => {
+                self.replay_path = Some(path);
+                Update::None
+            }
+            StreamStartMessage::ReplaySpeedPicked(new_speed) => {
+                self.replay_speed = new_speed;
+                Update::None
+            }
+            StreamStartMessage::ReplayLoopingToggled(new_looping) => {
+                self.replay_looping = new_looping;
+                Update::None
+            }
+            StreamStartMessage::SerialSettingsUpdated(new_settings) => {
+                Update::SerialSettingsUpdated(new_settings)
+            }
         }
     }
 }
@@ -0,0 +1,87 @@
+use std::{error::Error, path::PathBuf};
+
+use crate::backend::{
+    capture::Recorder,
+    profile::Profile,
+    stream::{source::StreamInput, ActiveStream, WorkerEvent},
+};
+
+/// Runs a stream without a window, sending everything the UI would show to
+/// the log instead.
+///
+/// Returns when the process receives Ctrl+C.
+pub fn run(
+    profile: Profile,
+    input: StreamInput,
+    record: Option<PathBuf>,
+    unattended: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async move {
+        let mut active_stream =
+            ActiveStream::new(profile.clone(), input.clone(), Recorder::default())?;
+        if let Some(path) = record {
+            active_stream.start_recording(&path)?;
+        }
+        log::info!(
+            target: "headless",
+            "Stream started on {} with profile {}",
+            input,
+            profile.name
+        );
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let events = tokio::select! {
+                events = active_stream.read_events(16) => events,
+                _ = &mut ctrl_c => {
+                    log::info!(target: "headless", "Received Ctrl+C, ending stream");
+                    break;
+                }
+            };
+            for event in &events {
+                match event {
+                    WorkerEvent::LatencySampleEvent(sample, _, size) => log::debug!(
+                        target: "headless",
+                        "Sent {} B payload in {} ms",
+                        size,
+                        sample.latency.as_millis()
+                    ),
+                    WorkerEvent::SerialEvent(_) => {
+                        log::trace!(target: "headless", "Received new score data")
+                    }
+                    // logged by ActiveStream, or only of interest to the UI
+                    WorkerEvent::RawFieldsEvent(..)
+                    | WorkerEvent::ErrorEvent(_)
+                    | WorkerEvent::SportDetectionEvent(_)
+                    | WorkerEvent::ConnectionEvent(..) => {}
+                }
+            }
+            active_stream.update_from_events(events);
+
+            if active_stream.should_restart(unattended) {
+                log::error!(
+                    target: "headless",
+                    "Stream will be restarted due to volume of errors ({}) exceeding configured value ({}) (unattended mode)",
+                    active_stream.errors().len(),
+                    unattended.unwrap()
+                );
+                match ActiveStream::new(
+                    profile.clone(),
+                    input.clone(),
+                    active_stream.recorder().clone(),
+                ) {
+                    Ok(stream) => {
+                        active_stream = stream;
+                        log::info!(target: "headless", "Restarted stream successfully");
+                    }
+                    Err(err) => {
+                        log::error!(target: "headless", "Failed to restart stream: {}", err);
+                    }
+                }
+            }
+        }
+        Ok::<(), Box<dyn Error>>(())
+    })
+}
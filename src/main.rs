@@ -5,7 +5,18 @@ use std::{
     path::PathBuf,
 };
 
-use backend::stream::ActiveStream;
+use backend::{
+    capture::Recorder,
+    stream::{
+        replay::{ReplayOptions, ReplaySpeed},
+        serial::{
+            detect_serial_port, DataBits, FlowControl, Parity, SerialSettings, StopBits,
+            AUTO_SERIAL_PATH,
+        },
+        source::StreamInput,
+        ActiveStream,
+    },
+};
 use clap::Parser;
 use flexi_logger::{FileSpec, Logger, WriteMode};
 use frontend::{DaktronicsSingularUiApp, Screen};
@@ -13,6 +24,7 @@ use iced::{theme::Palette, window::icon, Color, Font, Size};
 
 mod backend;
 mod frontend;
+mod headless;
 mod mock;
 
 /// Links the output of an Daktronics AllSport 5000 to Singular.Live
@@ -30,15 +42,65 @@ struct Args {
     profile: Option<PathBuf>,
 
     /// Whether to start the stream immediately. Must be used with --profile
-    /// and --serial-path.
+    /// and --serial-path, --network or --replay.
     #[arg(short, long, default_value_t = false)]
     start: bool,
 
     /// What serial path (e.g. /dev/xxx or COM1 on Windows) to use, when --start
     /// is used.
+    ///
+    /// Use "auto" to listen on every port for a few seconds and pick the one
+    /// the console is sending packets on.
     #[arg(short = 'e', long)]
     serial_path: Option<String>,
 
+    /// What serial device server to read from instead of a local serial port,
+    /// when --start is used.
+    ///
+    /// Use tcp://host:port to connect to a device server or
+    /// udp://local_address:port to receive datagrams from one.
+    #[arg(short, long, value_parser = parse_network_input, conflicts_with = "serial_path")]
+    network: Option<StreamInput>,
+
+    /// Replay a .dsucap capture file instead of reading from a console, when
+    /// --start is used.
+    #[arg(long, conflicts_with_all = ["serial_path", "network"])]
+    replay: Option<PathBuf>,
+
+    /// How fast to replay the capture: 1, 2, 10 or step (advance one packet at
+    /// a time from the UI).
+    #[arg(long, default_value = "1", requires = "replay")]
+    replay_speed: ReplaySpeed,
+
+    /// Whether to start the replay over once it reaches the end.
+    #[arg(long, default_value_t = false, requires = "replay")]
+    replay_loop: bool,
+
+    /// Override the profile's serial baud rate.
+    #[arg(long)]
+    baud_rate: Option<u32>,
+
+    /// Override the profile's serial parity: none, odd or even.
+    #[arg(long)]
+    parity: Option<Parity>,
+
+    /// Override the profile's serial data bits: 5, 6, 7 or 8.
+    #[arg(long)]
+    data_bits: Option<DataBits>,
+
+    /// Override the profile's serial stop bits: 1 or 2.
+    #[arg(long)]
+    stop_bits: Option<StopBits>,
+
+    /// Override the profile's serial flow control: none, software or hardware.
+    #[arg(long)]
+    flow_control: Option<FlowControl>,
+
+    /// Record the raw traffic from the console to a .dsucap capture file at the
+    /// given path, when --start is used.
+    #[arg(short, long)]
+    record: Option<PathBuf>,
+
     /// Whether to start the program in fullscreen mode.
     #[arg(short, long, default_value_t = false)]
     fullscreen: bool,
@@ -58,13 +120,61 @@ struct Args {
     unattended: Option<usize>,
 }
 
+impl Args {
+    /// The input source passed on the command line, if any.
+    fn input(&self) -> Option<StreamInput> {
+        self.serial_path
+            .clone()
+            .map(StreamInput::Serial)
+            .or_else(|| self.network.clone())
+            .or_else(|| {
+                self.replay.clone().map(|path| {
+                    StreamInput::Replay(ReplayOptions {
+                        path,
+                        speed: self.replay_speed,
+                        looping: self.replay_loop,
+                    })
+                })
+            })
+    }
+
+    /// Applies the serial settings passed on the command line over the
+    /// profile's.
+    fn override_serial_settings(&self, settings: &mut SerialSettings) {
+        if let Some(baud_rate) = self.baud_rate {
+            settings.baud_rate = baud_rate;
+        }
+        if let Some(parity) = self.parity {
+            settings.parity = parity;
+        }
+        if let Some(data_bits) = self.data_bits {
+            settings.data_bits = data_bits;
+        }
+        if let Some(stop_bits) = self.stop_bits {
+            settings.stop_bits = stop_bits;
+        }
+        if let Some(flow_control) = self.flow_control {
+            settings.flow_control = flow_control;
+        }
+    }
+}
+
+fn parse_network_input(s: &str) -> Result<StreamInput, String> {
+    match s.parse()? {
+        StreamInput::Serial(_) => Err("expected a tcp:// or udp:// address".to_owned()),
+        input => Ok(input),
+    }
+}
+
 enum DSUError {
     Iced(iced::Error),
     HeadlessWithoutStart,
+    HeadlessReplayStep,
     StartWithoutConfigSerial,
     ProfileFileRead(std::io::Error),
     ProfileFileParse(serde_json::Error),
-    HeadlessNotSupported,
+    SerialPortDetection(Option<std::io::Error>),
+    Headless(Box<dyn std::error::Error>),
     LoggerInitialization(flexi_logger::FlexiLoggerError),
 }
 
@@ -73,9 +183,13 @@ impl Display for DSUError {
         match self {
             Self::Iced(iced_error) => write!(f, "error when running ui: {}", iced_error),
             Self::HeadlessWithoutStart => write!(f, "cannot run headless without using --start"),
+            Self::HeadlessReplayStep => write!(
+                f,
+                "cannot use --replay-speed step headless, since stepping is done from the UI"
+            ),
             Self::StartWithoutConfigSerial => write!(
                 f,
-                "cannot start automatically without a config file and serial path"
+                "cannot start automatically without a config file and serial path, network source or replay"
             ),
             Self::ProfileFileRead(io_error) => {
                 write!(f, "failed to open profile file: {}", io_error)
@@ -83,8 +197,15 @@ impl Display for DSUError {
             Self::ProfileFileParse(serde_error) => {
                 write!(f, "failed to parse profile file: {}", serde_error)
             }
-            Self::HeadlessNotSupported => {
-                write!(f, "headless mode is not supported yet")
+            Self::SerialPortDetection(Some(io_error)) => {
+                write!(f, "failed to detect the console's serial port: {}", io_error)
+            }
+            Self::SerialPortDetection(None) => write!(
+                f,
+                "couldn't find the console on any serial port; check the cable and serial settings"
+            ),
+            Self::Headless(headless_error) => {
+                write!(f, "error when running headless: {}", headless_error)
             }
             Self::LoggerInitialization(logger_error) => {
                 write!(f, "failed to initialize logger: {}", logger_error)
@@ -104,6 +225,7 @@ impl std::error::Error for DSUError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Self::Iced(iced_error) => Some(iced_error),
+            Self::Headless(headless_error) => Some(headless_error.as_ref()),
             _ => None,
         }
     }
@@ -129,11 +251,45 @@ fn main() -> Result<(), DSUError> {
     if args.headless && !args.start {
         return Err(DSUError::HeadlessWithoutStart);
     }
-    if args.start && (args.serial_path.is_none() || args.profile.is_none()) {
+    if args.headless && args.replay.is_some() && matches!(args.replay_speed, ReplaySpeed::Step) {
+        return Err(DSUError::HeadlessReplayStep);
+    }
+    if args.start && (args.input().is_none() || args.profile.is_none()) {
         return Err(DSUError::StartWithoutConfigSerial);
     }
+
+    let mut profile: backend::profile::Profile = if args.profile.is_some() {
+        backend::profile::Profile::import(
+            &std::fs::read_to_string(args.profile.as_deref().unwrap())
+                .map_err(DSUError::ProfileFileRead)?,
+        )
+        .map_err(DSUError::ProfileFileParse)?
+    } else {
+        Default::default()
+    };
+    args.override_serial_settings(&mut profile.serial);
+
+    let input = match args.input() {
+        Some(StreamInput::Serial(path)) if path == AUTO_SERIAL_PATH => {
+            let runtime = tokio::runtime::Runtime::new()
+                .map_err(|err| DSUError::SerialPortDetection(Some(err)))?;
+            let port = runtime
+                .block_on(detect_serial_port(profile.serial))
+                .ok_or(DSUError::SerialPortDetection(None))?;
+            log::info!("Detected the console on {}", port);
+            Some(StreamInput::Serial(port))
+        }
+        input => input,
+    };
+
     if args.headless {
-        return Err(DSUError::HeadlessNotSupported);
+        return headless::run(
+            profile,
+            input.expect("no input"),
+            args.record,
+            args.unattended,
+        )
+        .map_err(DSUError::Headless);
     }
 
     let program_icon = image::load_from_memory_with_format(
@@ -145,16 +301,6 @@ fn main() -> Result<(), DSUError> {
     let program_icon_width = program_icon.width();
     let program_icon_height = program_icon.height();
 
-    let profile: backend::profile::Profile = if args.profile.is_some() {
-        serde_json::from_str(
-            &std::fs::read_to_string(args.profile.as_deref().unwrap())
-                .map_err(DSUError::ProfileFileRead)?,
-        )
-        .map_err(DSUError::ProfileFileParse)?
-    } else {
-        Default::default()
-    };
-
     iced::application(
         |app: &DaktronicsSingularUiApp| {
             if matches!(app.screen, Screen::Welcome) {
@@ -215,9 +361,17 @@ fn main() -> Result<(), DSUError> {
         let screen = if args.start {
             match ActiveStream::new(
                 profile.clone(),
-                args.serial_path.as_ref().expect("no tty path").clone(),
+                input.clone().expect("no input"),
+                Recorder::default(),
             ) {
-                Ok(stream) => frontend::Screen::stream_running(stream),
+                Ok(stream) => {
+                    if let Some(ref path) = args.record {
+                        if let Err(err) = stream.start_recording(path) {
+                            log::error!("Couldn't start recording: {}", err);
+                        }
+                    }
+                    frontend::Screen::stream_running(stream)
+                }
                 Err(err) => {
                     log::error!("Couldn't start stream: {}", err);
                     immediately_exit = true;
@@ -234,7 +388,7 @@ fn main() -> Result<(), DSUError> {
                 profile: profile.clone(),
                 screen,
                 hide_header: args.hide_header,
-                initial_tty_path: args.serial_path.clone(),
+                initial_input: input.clone(),
                 unattended: args.unattended,
                 ..Default::default()
             },